@@ -0,0 +1,113 @@
+use std::fmt;
+use web3::types::U256;
+
+/// Reasons a locked ERC20 amount cannot be migrated as an SPL amount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountError {
+    /// Scaled amount does not fit in a `u64` SPL amount.
+    Overflow,
+    /// Amount carries precision below the smallest SPL unit.
+    PrecisionLoss,
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmountError::Overflow => write!(f, "amount does not fit in a u64 SPL amount"),
+            AmountError::PrecisionLoss => {
+                write!(f, "amount loses precision when scaled to SPL decimals")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+/// Converts an ERC20 amount with `eth_decimals` into SPL base units with `sol_decimals`.
+pub fn convert_amount(
+    amount: U256,
+    eth_decimals: u8,
+    sol_decimals: u8,
+) -> Result<u64, AmountError> {
+    let scaled = if sol_decimals >= eth_decimals {
+        let factor = U256::from(10)
+            .checked_pow(U256::from(sol_decimals - eth_decimals))
+            .ok_or(AmountError::Overflow)?;
+        amount.checked_mul(factor).ok_or(AmountError::Overflow)?
+    } else {
+        let factor = U256::from(10)
+            .checked_pow(U256::from(eth_decimals - sol_decimals))
+            .ok_or(AmountError::Overflow)?;
+        if !(amount % factor).is_zero() {
+            return Err(AmountError::PrecisionLoss);
+        }
+        amount / factor
+    };
+
+    if scaled > U256::from(u64::MAX) {
+        return Err(AmountError::Overflow);
+    }
+
+    Ok(scaled.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::decode_lock;
+    use crate::bridge::tests::lock_log;
+    use web3::types::H160;
+
+    #[test]
+    fn scales_down_exact_amounts() {
+        assert_eq!(convert_amount(U256::exp10(18), 18, 9), Ok(1_000_000_000));
+        assert_eq!(convert_amount(U256::exp10(9), 18, 9), Ok(1));
+        assert_eq!(convert_amount(U256::zero(), 18, 9), Ok(0));
+    }
+
+    #[test]
+    fn refuses_precision_below_the_smallest_spl_unit() {
+        assert_eq!(
+            convert_amount(U256::exp10(9) - 1, 18, 9),
+            Err(AmountError::PrecisionLoss)
+        );
+        assert_eq!(
+            convert_amount(U256::exp10(18) + 1, 18, 9),
+            Err(AmountError::PrecisionLoss)
+        );
+    }
+
+    #[test]
+    fn scales_up_and_keeps_equal_decimals() {
+        assert_eq!(convert_amount(U256::from(7), 6, 9), Ok(7_000));
+        assert_eq!(convert_amount(U256::from(7), 9, 9), Ok(7));
+    }
+
+    #[test]
+    fn refuses_amounts_past_u64() {
+        assert_eq!(convert_amount(U256::from(u64::MAX), 9, 9), Ok(u64::MAX));
+        assert_eq!(
+            convert_amount(U256::from(u64::MAX) + 1, 9, 9),
+            Err(AmountError::Overflow)
+        );
+        assert_eq!(
+            convert_amount(U256::from(u64::MAX), 9, 10),
+            Err(AmountError::Overflow)
+        );
+        assert_eq!(
+            convert_amount(U256::one(), 0, 255),
+            Err(AmountError::Overflow)
+        );
+        assert_eq!(convert_amount(U256::exp10(30), 30, 0), Ok(1));
+    }
+
+    #[test]
+    fn converts_the_amount_of_an_encoded_lock() {
+        let user = H160::repeat_byte(0x42);
+        let log = lock_log(U256::exp10(18) * 25 / 10, user, "dest");
+        let (eth_amount, eth_user, _) = decode_lock(&log).unwrap();
+
+        assert_eq!(eth_user, user);
+        assert_eq!(convert_amount(eth_amount, 18, 9), Ok(2_500_000_000));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::endpoints::FailoverHttp;
+use crate::gate::Gate;
+use crate::notify::{Incident, Notifier};
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use web3::types::{H160, U256};
+
+const LOW_VOIP_REASON: &str = "admin VOIP balance below threshold";
+const LOW_SOL_REASON: &str = "admin SOL balance below threshold";
+const LOW_ETH_REASON: &str = "admin ETH balance below threshold";
+
+/// Minimum admin balances below which processing is halted.
+pub struct BalanceThresholds {
+    /// VOIP held by the admin ATA, in SPL base units.
+    pub min_admin_voip: u64,
+    /// SOL held by the admin wallet for rent and fees, in lamports.
+    pub min_admin_lamports: u64,
+    /// ETH held by the admin wallet for burn gas, in wei.
+    pub min_admin_wei: U256,
+}
+
+/// Current admin balances on both chains.
+pub struct Balances {
+    pub voip: u64,
+    pub lamports: u64,
+    pub wei: U256,
+}
+
+/// Guards migrations and burns against draining the admin accounts.
+pub struct BalanceGuard<'a> {
+    pub admin_ata: Pubkey,
+    pub sol_admin: Pubkey,
+    pub eth_admin: H160,
+    pub thresholds: &'a BalanceThresholds,
+    pub notifier: &'a Notifier<'a>,
+    /// VOIP set aside for migrations in flight, not yet reflected in the admin ATA balance.
+    pub reserved: AtomicU64,
+}
+
+/// VOIP set aside for one migration, released when dropped.
+pub struct Reservation<'a> {
+    reserved: &'a AtomicU64,
+    amount: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.reserved.fetch_sub(self.amount, Ordering::SeqCst);
+    }
+}
+
+impl BalanceGuard<'_> {
+    /// Fetches the admin ATA, admin SOL and admin ETH balances.
+    pub async fn balances(
+        &self,
+        connection: &RpcClient,
+        eth: &web3::api::Eth<FailoverHttp>,
+    ) -> Result<Balances, Box<dyn std::error::Error>> {
+        let voip = connection
+            .get_token_account_balance(&self.admin_ata)
+            .await?
+            .amount
+            .parse::<u64>()?;
+        let lamports = connection.get_balance(&self.sol_admin).await?;
+        let wei = eth.balance(self.eth_admin, None).await?;
+
+        Ok(Balances {
+            voip,
+            lamports,
+            wei,
+        })
+    }
+
+    /// Closes `gate` for each balance below its threshold and reopens it once replenished.
+    pub async fn check(
+        &self,
+        gate: &Gate,
+        connection: &RpcClient,
+        eth: &web3::api::Eth<FailoverHttp>,
+    ) {
+        let balances = match self.balances(connection, eth).await {
+            Ok(balances) => balances,
+            Err(err) => {
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Failed to Check Admin Balances
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Failed❌
+                            Error:       {err}
+                "
+                );
+                return;
+            }
+        };
+
+        self.update_gate(
+            gate,
+            LOW_VOIP_REASON,
+            balances.voip < self.thresholds.min_admin_voip,
+            &format!("{} < {}", balances.voip, self.thresholds.min_admin_voip),
+        );
+        self.update_gate(
+            gate,
+            LOW_SOL_REASON,
+            balances.lamports < self.thresholds.min_admin_lamports,
+            &format!(
+                "{} < {} lamports",
+                balances.lamports, self.thresholds.min_admin_lamports
+            ),
+        );
+        self.update_gate(
+            gate,
+            LOW_ETH_REASON,
+            balances.wei < self.thresholds.min_admin_wei,
+            &format!("{} < {} wei", balances.wei, self.thresholds.min_admin_wei),
+        );
+    }
+
+    /// Re-checks balances every `interval`.
+    pub async fn monitor(
+        &self,
+        gate: &Gate,
+        connection: &RpcClient,
+        eth: &web3::api::Eth<FailoverHttp>,
+        interval: Duration,
+    ) {
+        loop {
+            tokio::time::sleep(interval).await;
+            self.check(gate, connection, eth).await;
+        }
+    }
+
+    /// Waits until the admin ATA covers `amount` on top of other migrations in flight, re-checking every `interval`, and reserves it.
+    pub async fn wait_for_voip(
+        &self,
+        connection: &RpcClient,
+        amount: u64,
+        interval: Duration,
+    ) -> Reservation<'_> {
+        let mut alerted = false;
+        loop {
+            match connection.get_token_account_balance(&self.admin_ata).await {
+                Ok(balance) => {
+                    if let Some(reservation) =
+                        self.reserve(balance.amount.parse::<u64>().unwrap_or(0), amount)
+                    {
+                        return reservation;
+                    }
+                    if !alerted {
+                        alerted = true;
+                        println!(
+                            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Admin ATA Cannot Cover Migration, Waiting
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Balance:     {}
+                            Reserved:    {}
+                            Amount:      {amount}
+                            Status:      Paused⏸️
+                ",
+                            balance.amount,
+                            self.reserved.load(Ordering::SeqCst)
+                        );
+                        self.notifier.notify(Incident {
+                            kind: "low_balance",
+                            subject: "admin ATA below migration amount".to_string(),
+                            title: "Admin ATA cannot cover migration".to_string(),
+                            detail: format!("Balance: {} < {amount}", balance.amount),
+                        });
+                    }
+                }
+                Err(err) => println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Failed to Check Admin ATA Balance
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Failed❌
+                            Error:       {err}
+                "
+                ),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Reserves `amount` when `balance` covers it along with every reservation held.
+    fn reserve(&self, balance: u64, amount: u64) -> Option<Reservation<'_>> {
+        self.reserved
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |reserved| {
+                let total = reserved.checked_add(amount)?;
+                (total <= balance).then_some(total)
+            })
+            .ok()?;
+        Some(Reservation {
+            reserved: &self.reserved,
+            amount,
+        })
+    }
+
+    fn update_gate(&self, gate: &Gate, reason: &'static str, low: bool, detail: &str) {
+        if low && gate.close(reason) {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Relayer Paused: {reason}
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Balance:     {detail}
+                            Status:      Paused⏸️
+                "
+            );
+            self.notifier.notify(Incident {
+                kind: "low_balance",
+                subject: reason.to_string(),
+                title: format!("Relayer paused: {reason}"),
+                detail: format!("Balance: {detail}"),
+            });
+        } else if !low && gate.open(reason) {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Relayer Resumed: {reason} cleared
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Resumed▶️
+                "
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::NotifySettings;
+
+    #[test]
+    fn reservations_share_one_balance() {
+        let notify_settings = NotifySettings {
+            webhook_urls: Vec::new(),
+            slack_webhook_urls: Vec::new(),
+            discord_webhook_urls: Vec::new(),
+            dedup_window: Duration::from_secs(60),
+            max_per_minute: 10,
+        };
+        let notifier = Notifier::new(&notify_settings);
+        let thresholds = BalanceThresholds {
+            min_admin_voip: 0,
+            min_admin_lamports: 0,
+            min_admin_wei: U256::zero(),
+        };
+        let guard = BalanceGuard {
+            admin_ata: Pubkey::default(),
+            sol_admin: Pubkey::default(),
+            eth_admin: H160::zero(),
+            thresholds: &thresholds,
+            notifier: &notifier,
+            reserved: AtomicU64::new(0),
+        };
+
+        let first = guard.reserve(100, 60).unwrap();
+        assert!(guard.reserve(100, 41).is_none());
+        let second = guard.reserve(100, 40).unwrap();
+        assert!(guard.reserve(100, 1).is_none());
+
+        drop(first);
+        assert!(guard.reserve(100, 60).is_some());
+        drop(second);
+        assert!(guard.reserve(u64::MAX, u64::MAX).is_some());
+        assert_eq!(guard.reserved.load(Ordering::SeqCst), 0);
+    }
+}
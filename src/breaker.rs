@@ -0,0 +1,356 @@
+use crate::notify::{Incident, Notifier};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// how often callers held by an open breaker look again
+const WAIT_POLL: Duration = Duration::from_secs(1);
+
+/// When a breaker trips and how long it stays open.
+pub struct BreakerSettings {
+    /// Consecutive failures that trip the breaker.
+    pub consecutive_failures: usize,
+    /// Failure percentage over the last `window` attempts that trips the breaker.
+    pub failure_rate_percent: usize,
+    pub window: usize,
+    /// Time open before a single probe is let through.
+    pub cooldown: Duration,
+}
+
+enum State {
+    Closed,
+    Open {
+        until: Instant,
+    },
+    /// A probe is in flight; its outcome closes or re-opens the breaker.
+    HalfOpen,
+}
+
+struct Inner {
+    state: State,
+    consecutive_failures: usize,
+    outcomes: VecDeque<bool>,
+    /// Bumped on every state change, so attempts started under an older state are not counted.
+    generation: u64,
+}
+
+/// Stops sending on one leg after repeated failures and probes it for recovery.
+pub struct Breaker<'a> {
+    pub leg: &'static str,
+    settings: &'a BreakerSettings,
+    notifier: &'a Notifier<'a>,
+    inner: Mutex<Inner>,
+}
+
+impl<'a> Breaker<'a> {
+    pub fn new(
+        leg: &'static str,
+        settings: &'a BreakerSettings,
+        notifier: &'a Notifier<'a>,
+    ) -> Self {
+        Self {
+            leg,
+            settings,
+            notifier,
+            inner: Mutex::new(Inner {
+                state: State::Closed,
+                consecutive_failures: 0,
+                outcomes: VecDeque::new(),
+                generation: 0,
+            }),
+        }
+    }
+
+    /// Waits until the leg may be used, returning the attempt whose outcome to record.
+    pub async fn acquire(&self) -> Attempt<'_, 'a> {
+        loop {
+            {
+                let mut inner = self.inner.lock().unwrap();
+                match inner.state {
+                    State::Closed => return self.attempt(inner.generation),
+                    State::Open { until } if Instant::now() >= until => {
+                        inner.state = State::HalfOpen;
+                        inner.generation += 1;
+                        println!(
+                            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Circuit Breaker Half-Open: {}
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Probing ♻️
+                ",
+                            self.leg
+                        );
+                        return self.attempt(inner.generation);
+                    }
+                    _ => {}
+                }
+            }
+            tokio::time::sleep(WAIT_POLL).await;
+        }
+    }
+
+    fn attempt(&self, generation: u64) -> Attempt<'_, 'a> {
+        Attempt {
+            breaker: self,
+            generation,
+            recorded: false,
+        }
+    }
+
+    /// Records the outcome of an attempt let through by [`Breaker::acquire`] at `generation`.
+    fn record(&self, generation: u64, success: bool) {
+        let mut inner = self.inner.lock().unwrap();
+
+        // an attempt started before a trip must not count as the probe's result
+        if generation != inner.generation {
+            return;
+        }
+
+        inner.outcomes.push_back(success);
+        while inner.outcomes.len() > self.settings.window {
+            inner.outcomes.pop_front();
+        }
+        inner.consecutive_failures = if success {
+            0
+        } else {
+            inner.consecutive_failures + 1
+        };
+
+        let failures = inner.outcomes.iter().filter(|success| !**success).count();
+        let window_full = inner.outcomes.len() >= self.settings.window;
+        let trip = inner.consecutive_failures >= self.settings.consecutive_failures
+            || (window_full
+                && failures * 100 >= self.settings.failure_rate_percent * inner.outcomes.len());
+
+        match inner.state {
+            State::HalfOpen if success => {
+                inner.state = State::Closed;
+                inner.generation += 1;
+                inner.consecutive_failures = 0;
+                inner.outcomes.clear();
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Circuit Breaker Closed: {}
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Resumed▶️
+                ",
+                    self.leg
+                );
+            }
+            State::HalfOpen => self.open(&mut inner, "probe failed"),
+            State::Closed if trip => {
+                let reason = format!(
+                    "{} consecutive failures, {failures} of last {} attempts failed",
+                    inner.consecutive_failures,
+                    inner.outcomes.len()
+                );
+                self.open(&mut inner, &reason);
+            }
+            _ => {}
+        }
+    }
+
+    /// Lets another probe through when the one started at `generation` ended without an outcome.
+    fn release(&self, generation: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if generation == inner.generation && matches!(inner.state, State::HalfOpen) {
+            inner.state = State::Open {
+                until: Instant::now(),
+            };
+            inner.generation += 1;
+        }
+    }
+
+    /// Whether the leg is currently not sending.
+    pub fn is_open(&self) -> bool {
+        !matches!(self.inner.lock().unwrap().state, State::Closed)
+    }
+
+    fn open(&self, inner: &mut Inner, reason: &str) {
+        inner.state = State::Open {
+            until: Instant::now() + self.settings.cooldown,
+        };
+        inner.generation += 1;
+        println!(
+            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Circuit Breaker Tripped: {}
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Reason:      {reason}
+                            Retry In:    {:?}
+                            Status:      Halted🛑
+                ",
+            self.leg, self.settings.cooldown
+        );
+        self.notifier.notify(Incident {
+            kind: "breaker_tripped",
+            subject: self.leg.to_string(),
+            title: format!("Circuit breaker tripped: {}", self.leg),
+            detail: format!("{reason}\nRetry in {:?}", self.settings.cooldown),
+        });
+    }
+}
+
+/// A use of the leg let through by [`Breaker::acquire`], released as a probe if dropped without an outcome.
+pub struct Attempt<'b, 'a> {
+    breaker: &'b Breaker<'a>,
+    generation: u64,
+    recorded: bool,
+}
+
+impl Attempt<'_, '_> {
+    /// Records whether the attempt succeeded.
+    pub fn record(mut self, success: bool) {
+        self.recorded = true;
+        self.breaker.record(self.generation, success);
+    }
+}
+
+impl Drop for Attempt<'_, '_> {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.breaker.release(self.generation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::NotifySettings;
+
+    fn notify_settings() -> NotifySettings {
+        NotifySettings {
+            webhook_urls: Vec::new(),
+            slack_webhook_urls: Vec::new(),
+            discord_webhook_urls: Vec::new(),
+            dedup_window: Duration::from_secs(60),
+            max_per_minute: 10,
+        }
+    }
+
+    fn settings(cooldown: Duration) -> BreakerSettings {
+        BreakerSettings {
+            consecutive_failures: 3,
+            failure_rate_percent: 50,
+            window: 10,
+            cooldown,
+        }
+    }
+
+    #[tokio::test]
+    async fn trips_on_consecutive_failures() {
+        let notify_settings = notify_settings();
+        let notifier = Notifier::new(&notify_settings);
+        let settings = settings(Duration::from_secs(60));
+        let breaker = Breaker::new("test", &settings, &notifier);
+
+        for _ in 0..2 {
+            let attempt = breaker.acquire().await;
+            attempt.record(false);
+        }
+        assert!(!breaker.is_open());
+
+        let attempt = breaker.acquire().await;
+        attempt.record(true);
+        for _ in 0..2 {
+            let attempt = breaker.acquire().await;
+            attempt.record(false);
+        }
+        assert!(!breaker.is_open(), "a success resets the consecutive count");
+
+        let attempt = breaker.acquire().await;
+        attempt.record(false);
+        assert!(breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn trips_on_failure_rate_once_the_window_is_full() {
+        let notify_settings = notify_settings();
+        let notifier = Notifier::new(&notify_settings);
+        let settings = settings(Duration::from_secs(60));
+        let breaker = Breaker::new("test", &settings, &notifier);
+
+        for success in [true, false, true, false, true, false, true, false, true] {
+            let attempt = breaker.acquire().await;
+            attempt.record(success);
+        }
+        assert!(!breaker.is_open());
+
+        let attempt = breaker.acquire().await;
+        attempt.record(false);
+        assert!(breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn probe_closes_or_reopens_after_the_cooldown() {
+        let notify_settings = notify_settings();
+        let notifier = Notifier::new(&notify_settings);
+        let settings = settings(Duration::ZERO);
+        let breaker = Breaker::new("test", &settings, &notifier);
+
+        for _ in 0..3 {
+            let attempt = breaker.acquire().await;
+            attempt.record(false);
+        }
+        assert!(breaker.is_open());
+
+        let probe = breaker.acquire().await;
+        probe.record(false);
+        assert!(breaker.is_open(), "a failed probe re-opens the breaker");
+
+        let probe = breaker.acquire().await;
+        probe.record(true);
+        assert!(!breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn ignores_attempts_started_before_a_trip() {
+        let notify_settings = notify_settings();
+        let notifier = Notifier::new(&notify_settings);
+        let settings = settings(Duration::ZERO);
+        let breaker = Breaker::new("test", &settings, &notifier);
+
+        let slow = breaker.acquire().await;
+        let slower = breaker.acquire().await;
+        for _ in 0..3 {
+            let attempt = breaker.acquire().await;
+            attempt.record(false);
+        }
+        let probe = breaker.acquire().await;
+
+        // the slow attempt finishing during the probe is not the probe's result
+        slow.record(true);
+        assert!(breaker.is_open());
+
+        probe.record(false);
+        let probe = breaker.acquire().await;
+        slower.record(false);
+        probe.record(true);
+        assert!(!breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn a_probe_dropped_without_an_outcome_lets_another_through() {
+        let notify_settings = notify_settings();
+        let notifier = Notifier::new(&notify_settings);
+        let settings = settings(Duration::ZERO);
+        let breaker = Breaker::new("test", &settings, &notifier);
+
+        for _ in 0..3 {
+            let attempt = breaker.acquire().await;
+            attempt.record(false);
+        }
+
+        // a probe whose caller went away, e.g. when stepping down
+        let probe = breaker.acquire().await;
+        drop(probe);
+
+        let probe = tokio::time::timeout(Duration::from_millis(100), breaker.acquire())
+            .await
+            .expect("another probe is let through");
+        probe.record(true);
+        assert!(!breaker.is_open());
+    }
+}
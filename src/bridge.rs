@@ -0,0 +1,245 @@
+use crate::endpoints::FailoverHttp;
+use crate::gate::Gate;
+use hex_literal::hex;
+use std::time::Duration;
+use web3::contract::{Contract, Options};
+use web3::ethabi::{decode, ParamType};
+use web3::types::{Address, Log, TransactionReceipt, H160, U256};
+
+pub const BRIDGE_PAUSED_REASON: &str = "bridge contract paused";
+
+// this is 'TokensLocked(uint256 amount, address indexed user, string solanaAddress, uint256 timestamp)' event
+// use https://emn178.github.io/online-tools/keccak_256.html,
+// and type in 'TokensLocked(uint256,address,string,uint256)' (without the quote)
+// it will return result hash as used in next line
+pub const TOKENS_LOCKED_TOPIC: [u8; 32] =
+    hex!("a3c29410d4173cda5ec6e52fca2d334b67df70664e718bee6d216e089b408442");
+
+// this is 'TokensBurned(address indexed user, uint256 amount, uint256 timestamp)' event
+// hash of 'TokensBurned(address,uint256,uint256)'
+pub const TOKENS_BURNED_TOPIC: [u8; 32] =
+    hex!("ccbea4088a3b7ae9ca2d15fab9a9742a4075b4d7247768a1eecea917565aba00");
+
+// this is 'OwnershipTransferred(address indexed previousOwner, address indexed newOwner)' event
+// hash of 'OwnershipTransferred(address,address)'
+pub const OWNERSHIP_TRANSFERRED_TOPIC: [u8; 32] =
+    hex!("8be0079c531659141344cd1fd0a4f28419497f9722a3daafe3b4186f6b6457e0");
+
+/// Mirrors the bridge's `contractIsPaused()` flag onto `gate`.
+pub async fn check_pause(gate: &Gate, contract: &Contract<FailoverHttp>) {
+    let paused: bool = match contract
+        .query("contractIsPaused", (), None, Options::default(), None)
+        .await
+    {
+        Ok(paused) => paused,
+        Err(err) => {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Failed to Read Bridge Pause State
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Failed❌
+                            Error:       {err}
+                "
+            );
+            return;
+        }
+    };
+
+    if paused && gate.close(BRIDGE_PAUSED_REASON) {
+        println!(
+            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Bridge Paused, Holding New Events
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Paused⏸️
+                "
+        );
+    } else if !paused && gate.open(BRIDGE_PAUSED_REASON) {
+        println!(
+            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Bridge Unpaused, Resuming Events
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Resumed▶️
+                "
+        );
+    }
+}
+
+/// Polls the bridge pause flag every `interval`.
+pub async fn monitor_pause(gate: &Gate, contract: &Contract<FailoverHttp>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        check_pause(gate, contract).await;
+    }
+}
+
+/// Logs an `OwnershipTransferred` event from the bridge.
+pub fn report_ownership_transferred(log: &Log) {
+    let previous_owner = log.topics.get(1).map(|topic| H160::from(*topic));
+    let new_owner = log.topics.get(2).map(|topic| H160::from(*topic));
+    let transaction_hash = log.transaction_hash;
+
+    println!(
+        "
+            !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
+                    BRIDGE OWNERSHIP TRANSFERRED ⚠️
+            !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
+                    Previous Owner:  {previous_owner:?}
+                    New Owner:       {new_owner:?}
+                    Tx Hash:         {transaction_hash:?}
+                    Status:          Re-checking relayer roles ♻️
+        "
+    );
+}
+
+/// Checks `receipt` carries a `TokensBurned` event from `bridge` for `user` and `amount`.
+pub fn verify_burn(
+    receipt: &TransactionReceipt,
+    bridge: Address,
+    user: H160,
+    amount: U256,
+) -> Result<(), String> {
+    let burned: Vec<(H160, U256)> = receipt
+        .logs
+        .iter()
+        .filter(|log| {
+            log.address == bridge && log.topics.first() == Some(&TOKENS_BURNED_TOPIC.into())
+        })
+        .filter_map(|log| {
+            let burned_user = H160::from(*log.topics.get(1)?);
+            let data = decode(&[ParamType::Uint(256), ParamType::Uint(256)], &log.data.0).ok()?;
+            let burned_amount = data.first()?.clone().into_uint()?;
+            Some((burned_user, burned_amount))
+        })
+        .collect();
+
+    if burned.contains(&(user, amount)) {
+        return Ok(());
+    }
+
+    match burned.first() {
+        Some((burned_user, burned_amount)) => Err(format!(
+            "TokensBurned mismatch: expected {amount} for {user:?}, burned {burned_amount} for {burned_user:?}"
+        )),
+        None => Err("burn receipt has no TokensBurned event".to_string()),
+    }
+}
+
+/// Decodes amount, user and Solana address from a `TokensLocked` event in `log`.
+pub fn decode_lock(log: &Log) -> Option<(U256, H160, String)> {
+    let lock = decode_lock_fields(log);
+    if lock.is_none() {
+        println!(
+            "
+                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                        Failed to Decode Event
+                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                        Tx Hash:     {:?}
+                                        Status:      Failed❌
+                                ",
+            log.transaction_hash
+        );
+    }
+    lock
+}
+
+// only the user is indexed; amount, solanaAddress and timestamp are ABI-encoded in data
+fn decode_lock_fields(log: &Log) -> Option<(U256, H160, String)> {
+    let eth_address = H160::from(*log.topics.get(1)?);
+    let mut data = decode(
+        &[
+            ParamType::Uint(256),
+            ParamType::String,
+            ParamType::Uint(256),
+        ],
+        &log.data.0,
+    )
+    .ok()?
+    .into_iter();
+    let eth_amount = data.next()?.into_uint()?;
+    let solana_address = data.next()?.into_string()?;
+
+    if eth_amount.is_zero() || eth_address.is_zero() || solana_address.is_empty() {
+        return None;
+    }
+    Some((eth_amount, eth_address, solana_address))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use web3::ethabi::{encode, Token};
+    use web3::types::{Bytes, H256};
+
+    /// A `TokensLocked` log as the bridge emits it.
+    pub fn lock_log(amount: U256, user: H160, solana_address: &str) -> Log {
+        Log {
+            address: H160::repeat_byte(0xb1),
+            topics: vec![TOKENS_LOCKED_TOPIC.into(), H256::from(user)],
+            data: Bytes(encode(&[
+                Token::Uint(amount),
+                Token::String(solana_address.to_string()),
+                Token::Uint(U256::from(1_700_000_000u64)),
+            ])),
+            block_hash: None,
+            block_number: Some(100.into()),
+            transaction_hash: Some(H256::repeat_byte(0x11)),
+            transaction_index: None,
+            log_index: Some(0.into()),
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    #[test]
+    fn decodes_a_real_lock() {
+        let user = H160::repeat_byte(0x42);
+        let amount = U256::exp10(18) * 5;
+        let log = lock_log(amount, user, "So11111111111111111111111111111111111111112");
+
+        assert_eq!(
+            decode_lock(&log),
+            Some((
+                amount,
+                user,
+                "So11111111111111111111111111111111111111112".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_locks_without_panicking() {
+        let user = H160::repeat_byte(0x42);
+        let mut missing_user = lock_log(U256::one(), user, "dest");
+        missing_user.topics.truncate(1);
+        assert_eq!(decode_lock(&missing_user), None);
+
+        let mut short_data = lock_log(U256::one(), user, "dest");
+        short_data.data.0.truncate(40);
+        assert_eq!(decode_lock(&short_data), None);
+
+        assert_eq!(decode_lock(&lock_log(U256::zero(), user, "dest")), None);
+        assert_eq!(decode_lock(&lock_log(U256::one(), user, "")), None);
+    }
+
+    #[test]
+    fn verifies_the_matching_burn() {
+        let bridge = H160::repeat_byte(0xb1);
+        let user = H160::repeat_byte(0x42);
+        let receipt = TransactionReceipt {
+            logs: vec![Log {
+                address: bridge,
+                topics: vec![TOKENS_BURNED_TOPIC.into(), H256::from(user)],
+                data: Bytes(encode(&[Token::Uint(7.into()), Token::Uint(1.into())])),
+                ..lock_log(U256::one(), user, "dest")
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(verify_burn(&receipt, bridge, user, 7.into()), Ok(()));
+        assert!(verify_burn(&receipt, bridge, user, 8.into()).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::balance::BalanceThresholds;
+use crate::breaker::BreakerSettings;
+use crate::limits::Limits;
+use crate::notify::NotifySettings;
+use crate::polling::{EventSource, PollSettings};
+use crate::server::AdminToken;
+use crate::simulation::ComputeBudget;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use std::env;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use web3::types::U256;
+
+/// Optional relayer settings read from the environment, with defaults.
+pub struct Settings {
+    /// Off-curve destinations (e.g. multisig vaults) explicitly allowed to receive migrations.
+    pub sol_off_curve_allowlist: Vec<Pubkey>,
+    /// Admin balances below which processing pauses.
+    pub balance_thresholds: BalanceThresholds,
+    pub balance_check_interval: Duration,
+    pub bridge_pause_poll_interval: Duration,
+    pub health_log_interval: Duration,
+    pub role_check_interval: Duration,
+    pub ledger_path: String,
+    /// Address serving `/metrics`, disabled when unset.
+    pub http_addr: Option<SocketAddr>,
+    pub supply_check_interval: Duration,
+    /// Allowed bridge balance drift from recorded transfers, in ERC20 base units.
+    pub supply_lock_drift_tolerance: U256,
+    /// Allowed admin ATA outflow drift from recorded migrations, in SPL base units.
+    pub supply_migration_drift_tolerance: u64,
+    /// Amount ceilings above which transfers are held for approval.
+    pub limits: Limits,
+    /// Bearer token the held queue commands send.
+    pub admin_token: Option<String>,
+    /// Operator tokens the held queue endpoints accept, which are disabled when empty.
+    pub admin_tokens: Vec<AdminToken>,
+    /// JSON deny and allow lists, reloaded when modified.
+    pub screening_path: Option<String>,
+    /// When the migrate and burn circuit breakers trip.
+    pub breaker: BreakerSettings,
+    /// Where incident notifications are posted.
+    pub notify: NotifySettings,
+    /// Reconnects within `reconnect_storm_window` that raise a notification.
+    pub reconnect_storm_count: usize,
+    pub reconnect_storm_window: Duration,
+    /// Simulate transactions instead of broadcasting them, keeping the ledger in memory.
+    pub dry_run: bool,
+    /// Partition a dry run follows, since it never takes a lease.
+    pub dry_run_partition: u64,
+    /// Compute units requested for migrate transactions, from simulation.
+    pub compute_budget: ComputeBudget,
+    /// Migration program error names or numbers refunded rather than held.
+    pub migrate_refund_error_codes: Vec<String>,
+    /// Blocks an Ethereum endpoint may trail the best one before it is passed over.
+    pub eth_max_lag_blocks: u64,
+    /// Slots a Solana endpoint may trail the best one before it is passed over.
+    pub sol_max_lag_slots: u64,
+    pub endpoint_health_interval: Duration,
+    /// Ethereum endpoints that must return a lock's receipt before it is relayed.
+    pub eth_lock_quorum: usize,
+    pub event_source: EventSource,
+    pub poll: PollSettings,
+    /// How long `auto` polls before trying the websocket again.
+    pub websocket_retry_interval: Duration,
+    /// How long the websocket may go without a new head while the chain advances.
+    pub heads_stall_timeout: Duration,
+    /// Lease file shared by all instances, so only its holder relays; every instance relays when unset.
+    pub leader_lease_path: Option<String>,
+    pub leader_lease_ttl: Duration,
+    /// Names this instance in the lease, the hostname by default.
+    pub instance_id: String,
+    /// Instances sharing the locks by Ethereum user, each holding one partition's lease.
+    pub partitions: u64,
+    pub resume_interval: Duration,
+    /// How long a pending or migrated transfer goes untouched before it is resumed.
+    pub resume_after: Duration,
+}
+
+impl Settings {
+    pub fn from_env() -> Self {
+        Self {
+            sol_off_curve_allowlist: env_list("SOL_OFF_CURVE_DESTINATION_ALLOWLIST"),
+            balance_thresholds: BalanceThresholds {
+                min_admin_voip: env_or("SOL_ADMIN_MIN_VOIP_BALANCE", 0),
+                min_admin_lamports: env_or("SOL_ADMIN_MIN_LAMPORTS", 10_000_000),
+                min_admin_wei: env_u256("ETH_ADMIN_MIN_WEI", U256::exp10(16)),
+            },
+            balance_check_interval: env_secs("BALANCE_CHECK_INTERVAL_SECS", 60),
+            bridge_pause_poll_interval: env_secs("BRIDGE_PAUSE_POLL_INTERVAL_SECS", 15),
+            health_log_interval: env_secs("HEALTH_LOG_INTERVAL_SECS", 300),
+            role_check_interval: env_secs("ROLE_CHECK_INTERVAL_SECS", 300),
+            ledger_path: env_or("RELAYER_LEDGER_PATH", "ledger.json".to_string()),
+            http_addr: env_opt("RELAYER_HTTP_ADDR"),
+            supply_check_interval: env_secs("SUPPLY_CHECK_INTERVAL_SECS", 60),
+            supply_lock_drift_tolerance: env_u256("SUPPLY_LOCK_DRIFT_TOLERANCE", U256::zero()),
+            supply_migration_drift_tolerance: env_or("SUPPLY_MIGRATION_DRIFT_TOLERANCE", 0),
+            limits: Limits {
+                max_transfer: env_opt("MAX_TRANSFER_AMOUNT"),
+                user_hourly: env_opt("USER_HOURLY_LIMIT"),
+                user_daily: env_opt("USER_DAILY_LIMIT"),
+                global_hourly: env_opt("GLOBAL_HOURLY_LIMIT"),
+                global_daily: env_opt("GLOBAL_DAILY_LIMIT"),
+            },
+            admin_token: env_opt("RELAYER_ADMIN_TOKEN"),
+            // the shared token decides as `admin`, named tokens as their operator
+            admin_tokens: env_list("RELAYER_ADMIN_TOKENS")
+                .into_iter()
+                .chain(env_opt("RELAYER_ADMIN_TOKEN").map(|token| AdminToken {
+                    actor: "admin".to_string(),
+                    token,
+                }))
+                .collect(),
+            screening_path: env_opt("SCREENING_LIST_PATH"),
+            breaker: BreakerSettings {
+                consecutive_failures: env_or("BREAKER_CONSECUTIVE_FAILURES", 5),
+                failure_rate_percent: env_or("BREAKER_FAILURE_RATE_PERCENT", 50),
+                window: env_or("BREAKER_WINDOW", 20),
+                cooldown: env_secs("BREAKER_COOLDOWN_SECS", 60),
+            },
+            notify: NotifySettings {
+                webhook_urls: env_list("NOTIFY_WEBHOOK_URLS"),
+                slack_webhook_urls: env_list("NOTIFY_SLACK_WEBHOOK_URLS"),
+                discord_webhook_urls: env_list("NOTIFY_DISCORD_WEBHOOK_URLS"),
+                dedup_window: env_secs("NOTIFY_DEDUP_SECS", 300),
+                max_per_minute: env_or("NOTIFY_MAX_PER_MINUTE", 20),
+            },
+            reconnect_storm_count: env_or("RECONNECT_STORM_COUNT", 5),
+            reconnect_storm_window: env_secs("RECONNECT_STORM_WINDOW_SECS", 600),
+            dry_run: env_or("DRY_RUN", false),
+            dry_run_partition: env_or("DRY_RUN_PARTITION", 0),
+            compute_budget: ComputeBudget {
+                margin_percent: env_or("MIGRATE_COMPUTE_UNIT_MARGIN_PERCENT", 20),
+                unit_price: env_opt("MIGRATE_COMPUTE_UNIT_PRICE"),
+            },
+            migrate_refund_error_codes: env_list("MIGRATE_REFUND_ERROR_CODES"),
+            eth_max_lag_blocks: env_or("ETH_MAX_LAG_BLOCKS", 5),
+            sol_max_lag_slots: env_or("SOL_MAX_LAG_SLOTS", 150),
+            endpoint_health_interval: env_secs("ENDPOINT_HEALTH_INTERVAL_SECS", 30),
+            eth_lock_quorum: env_or("ETH_LOCK_QUORUM", 1),
+            event_source: env_or("EVENT_SOURCE", EventSource::Auto),
+            poll: PollSettings {
+                interval: env_secs("LOG_POLL_INTERVAL_SECS", 12),
+                max_blocks: env_or("LOG_POLL_MAX_BLOCKS", 1000),
+                start_block: env_opt("LOG_POLL_START_BLOCK"),
+            },
+            websocket_retry_interval: env_secs("WEBSOCKET_RETRY_SECS", 300),
+            heads_stall_timeout: env_secs("WEBSOCKET_STALL_SECS", 60),
+            leader_lease_path: env_opt("LEADER_LEASE_PATH"),
+            leader_lease_ttl: env_secs("LEADER_LEASE_TTL_SECS", 30),
+            instance_id: env_or(
+                "RELAYER_INSTANCE_ID",
+                env::var("HOSTNAME").unwrap_or_else(|_| std::process::id().to_string()),
+            ),
+            partitions: env_or("RELAYER_PARTITIONS", 1),
+            resume_interval: env_secs("RESUME_INTERVAL_SECS", 60),
+            resume_after: env_secs("RESUME_AFTER_SECS", 120),
+        }
+    }
+}
+
+/// Reads `name`, falling back to `default` when unset.
+pub fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| invalid(name)),
+        Err(_) => default,
+    }
+}
+
+/// Reads `name`, or `None` when unset.
+pub fn env_opt<T: FromStr>(name: &str) -> Option<T> {
+    env::var(name)
+        .ok()
+        .map(|value| value.trim().parse().unwrap_or_else(|_| invalid(name)))
+}
+
+/// Reads `name` as a number of seconds.
+pub fn env_secs(name: &str, default: u64) -> Duration {
+    Duration::from_secs(env_or(name, default))
+}
+
+/// Reads `name` as a decimal base-unit amount.
+pub fn env_u256(name: &str, default: U256) -> U256 {
+    match env::var(name) {
+        Ok(value) => U256::from_dec_str(value.trim()).unwrap_or_else(|_| invalid(name)),
+        Err(_) => default,
+    }
+}
+
+/// Reads `name` as a comma-separated list, empty when unset.
+pub fn env_list<T: FromStr>(name: &str) -> Vec<T> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().unwrap_or_else(|_| invalid(name)))
+        .collect()
+}
+
+fn invalid(name: &str) -> ! {
+    panic!(
+        "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Failed to parse {name}
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        "
+    )
+}
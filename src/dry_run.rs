@@ -0,0 +1,242 @@
+use crate::ledger::{now, AuditEntry, Decision, Transfer};
+use crate::relay::{migrate_instructions, Relayer};
+use crate::simulation::{program_error, simulate};
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use web3::contract::Options;
+use web3::types::H160;
+
+/// Audit actor for outcomes simulated in dry run.
+const DRY_RUN_ACTOR: &str = "dry run";
+
+/// Whether a dry run already simulated the refund of rejected `transfer`.
+pub fn refund_simulated(transfer: &Transfer) -> bool {
+    transfer
+        .audit
+        .iter()
+        .any(|entry| entry.actor == DRY_RUN_ACTOR)
+}
+
+/// Records on `transfer` the outcome of simulating its refund, `err` being the revert if any.
+fn record_simulated_refund(transfer: &mut Transfer, err: Option<String>) {
+    let outcome = match &err {
+        Some(err) => format!("refund would fail: {err}"),
+        None => "refund simulated".to_string(),
+    };
+    transfer.error = err.map(|err| format!("dry run: refund {err}"));
+    transfer.audit.push(AuditEntry {
+        at: now(),
+        actor: DRY_RUN_ACTOR.to_string(),
+        action: Decision::Annotate,
+        note: Some(outcome),
+    });
+}
+
+impl Relayer<'_> {
+    /// Simulates the migrate and estimates the burn for transfer `id` without broadcasting either.
+    pub async fn simulate_migrate_and_burn(
+        &self,
+        id: &str,
+        eth_address: H160,
+        solana_address: Pubkey,
+        migrate_amount: u64,
+    ) {
+        let simulation = match migrate_instructions(
+            self.connection,
+            self.program,
+            &self.state_pda,
+            &self.sol_voip_token_mint,
+            &self.voip_migration_program_id,
+            &self.sol_admin_pubkey,
+            &self.admin_ata,
+            &solana_address,
+            &self.token_program_id,
+            &self.associated_token_program_id,
+            &self.system_program_id,
+            migrate_amount,
+        )
+        .await
+        {
+            Ok(instructions) => {
+                simulate(
+                    self.connection,
+                    self.sol_admin_keypair,
+                    &self.settings.compute_budget,
+                    &instructions,
+                )
+                .await
+            }
+            Err(err) => Err(err),
+        };
+
+        let migrate_error = match simulation {
+            Ok(result) => {
+                let (status, error) = match (&result.err, program_error(&result)) {
+                    (Some(_), Some(program_error)) => (
+                        "Failed❌",
+                        format!("migration program error {program_error}"),
+                    ),
+                    (Some(err), None) => ("Failed❌", err.to_string()),
+                    (None, _) => ("Success✅", String::new()),
+                };
+                let logs = result
+                    .logs
+                    .unwrap_or_default()
+                    .join("\n                            ");
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Dry Run: Migration Simulated
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Sol Address: {solana_address}
+                        Amount:      {migrate_amount}
+                        Units Used:  {}
+                        Status:      {status}
+                        Error:       {error}
+                        Logs:
+                            {logs}
+                ",
+                    result.units_consumed.unwrap_or_default()
+                );
+                result.err.map(|_| error)
+            }
+            Err(err) => {
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Dry Run: Failed to Simulate Migration
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Sol Address: {solana_address}
+                        Status:      Failed❌
+                        Error:       {err}
+                "
+                );
+                Some(err.to_string())
+            }
+        };
+
+        let burn_error = self
+            .estimate(id, "burnTokens", (eth_address, solana_address.to_string()))
+            .await;
+
+        // nothing was sent, so the transfer stays pending
+        self.ledger.update(id, |transfer| {
+            transfer.error = match (migrate_error, burn_error) {
+                (None, None) => None,
+                (migrate_error, burn_error) => Some(format!(
+                    "dry run: migrate {}, burn {}",
+                    migrate_error.as_deref().unwrap_or("ok"),
+                    burn_error.as_deref().unwrap_or("ok")
+                )),
+            };
+        });
+    }
+
+    /// Estimates the refund for transfer `id` without broadcasting it, recording the outcome so it is simulated once.
+    pub async fn simulate_refund(&self, id: &str, eth_address: H160, solana_address: &str) {
+        let err = self
+            .estimate(
+                id,
+                "unlockTokens",
+                (eth_address, solana_address.to_string()),
+            )
+            .await;
+
+        self.ledger
+            .update(id, |transfer| record_simulated_refund(transfer, err));
+    }
+
+    /// Runs `function` through `eth_estimateGas` as the relayer, returning the revert error if any.
+    async fn estimate(&self, id: &str, function: &str, params: (H160, String)) -> Option<String> {
+        match self
+            .contract
+            .estimate_gas(function, params, self.eth_relayer, Options::default())
+            .await
+        {
+            Ok(gas) => {
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Dry Run: {function} Estimated
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Gas:         {gas}
+                        Status:      Success✅
+                "
+                );
+                None
+            }
+            Err(err) => {
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Dry Run: {function} Would Fail
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Status:      Failed❌
+                        Error:       {err}
+                "
+                );
+                Some(err.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::tests::lock_log;
+    use crate::ledger::TransferStatus;
+    use web3::types::U256;
+
+    fn rejected() -> Transfer {
+        let user = H160::repeat_byte(0x42);
+        let log = lock_log(U256::from(5), user, "dest");
+        Transfer {
+            status: TransferStatus::Rejected,
+            ..Transfer::new(&log, user, "dest", U256::from(5))
+        }
+    }
+
+    #[test]
+    fn a_simulated_refund_is_marked_once_recorded() {
+        let mut transfer = rejected();
+        assert!(!refund_simulated(&transfer));
+
+        record_simulated_refund(&mut transfer, None);
+        assert!(refund_simulated(&transfer));
+        assert_eq!(transfer.status, TransferStatus::Rejected);
+        assert_eq!(transfer.error, None);
+        assert_eq!(transfer.audit[0].note.as_deref(), Some("refund simulated"));
+    }
+
+    #[test]
+    fn a_failing_refund_simulation_keeps_its_error() {
+        let mut transfer = rejected();
+        record_simulated_refund(&mut transfer, Some("execution reverted".to_string()));
+
+        assert!(refund_simulated(&transfer));
+        assert_eq!(
+            transfer.error.as_deref(),
+            Some("dry run: refund execution reverted")
+        );
+        assert_eq!(
+            transfer.audit[0].note.as_deref(),
+            Some("refund would fail: execution reverted")
+        );
+    }
+
+    #[test]
+    fn other_audit_entries_are_not_a_simulated_refund() {
+        let mut transfer = rejected();
+        transfer.audit.push(AuditEntry {
+            at: now(),
+            actor: "alice".to_string(),
+            action: Decision::Reject,
+            note: Some("refund simulated".to_string()),
+        });
+        assert!(!refund_simulated(&transfer));
+    }
+}
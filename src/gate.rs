@@ -0,0 +1,48 @@
+use std::collections::BTreeSet;
+use tokio::sync::watch;
+
+/// Named reasons that halt new migrations and burns until every one is cleared.
+pub struct Gate {
+    reasons: watch::Sender<BTreeSet<String>>,
+}
+
+impl Gate {
+    pub fn new() -> Self {
+        let (reasons, _) = watch::channel(BTreeSet::new());
+        Self { reasons }
+    }
+
+    /// Halts processing for `reason`, returning whether it was not already set.
+    pub fn close(&self, reason: &str) -> bool {
+        self.reasons
+            .send_if_modified(|reasons| reasons.insert(reason.to_string()))
+    }
+
+    /// Clears `reason`, returning whether it was set.
+    pub fn open(&self, reason: &str) -> bool {
+        self.reasons
+            .send_if_modified(|reasons| reasons.remove(reason))
+    }
+
+    /// Reasons processing is currently halted for.
+    pub fn reasons(&self) -> Vec<String> {
+        self.reasons.borrow().iter().cloned().collect()
+    }
+
+    /// Whether processing is halted for `reason`.
+    pub fn is_closed_for(&self, reason: &str) -> bool {
+        self.reasons.borrow().contains(reason)
+    }
+
+    /// Waits until no halt reason is set.
+    pub async fn wait_open(&self) {
+        let mut reasons = self.reasons.subscribe();
+        let _ = reasons.wait_for(|reasons| reasons.is_empty()).await;
+    }
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
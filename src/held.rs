@@ -0,0 +1,84 @@
+use crate::config::Settings;
+use reqwest::{Client, Method};
+use serde_json::json;
+
+/// Runs `held <list|approve|reject|annotate>` against the running relayer's HTTP server.
+pub async fn run_held(args: &[String], settings: &Settings) {
+    let usage = "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Usage: voip-relayer-rs held list
+                     voip-relayer-rs held approve <transfer_id> [note]
+                     voip-relayer-rs held reject <transfer_id> [note]
+                     voip-relayer-rs held annotate <transfer_id> <note>
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        ";
+
+    let (method, path, body) = match args {
+        [command] if command == "list" => (Method::GET, "/held".to_string(), None),
+        [command, id, note @ ..]
+            if matches!(command.as_str(), "approve" | "reject" | "annotate") =>
+        {
+            let note = (!note.is_empty()).then(|| note.join(" "));
+            let body = json!({ "note": note });
+            (Method::POST, format!("/held/{id}/{command}"), Some(body))
+        }
+        _ => panic!("{usage}"),
+    };
+    send_admin_request(settings, method, &path, body).await;
+}
+
+/// Sends an admin request to the running relayer's HTTP server and prints the response, exiting non-zero on failure.
+pub async fn send_admin_request(
+    settings: &Settings,
+    method: Method,
+    path: &str,
+    body: Option<serde_json::Value>,
+) {
+    let addr = settings.http_addr.expect(
+        "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              RELAYER_HTTP_ADDR Is Not Set
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        ",
+    );
+    let token = settings.admin_token.clone().unwrap_or_default();
+    let mut request = Client::new()
+        .request(method, format!("http://{addr}{path}"))
+        .bearer_auth(token);
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(err) => {
+            println!(
+                "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Failed to Reach Relayer
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Address:     {addr}
+                        Status:      Failed❌
+                        Error:       {err}
+            "
+            );
+            std::process::exit(2);
+        }
+    };
+
+    let status = response.status();
+    let body = response.bytes().await.unwrap_or_default();
+    match serde_json::from_slice::<serde_json::Value>(&body) {
+        Ok(value) => println!(
+            "{}",
+            serde_json::to_string_pretty(&value).unwrap_or_default()
+        ),
+        Err(_) => println!("{}", String::from_utf8_lossy(&body)),
+    }
+
+    if !status.is_success() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::ledger::now;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{ErrorKind, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Contents of the lease file.
+#[derive(Debug, Serialize, Deserialize)]
+struct LeaseFile {
+    holder: String,
+    /// Unix seconds after which another instance may take over.
+    expires_at: u64,
+}
+
+/// A leadership lease kept in a file shared by every relayer instance.
+pub struct Lease {
+    path: PathBuf,
+    holder: String,
+    ttl: Duration,
+}
+
+impl Lease {
+    pub fn new(path: impl Into<PathBuf>, holder: String, ttl: Duration) -> Self {
+        Self {
+            path: path.into(),
+            holder,
+            ttl,
+        }
+    }
+
+    /// Takes or renews the lease unless another instance holds it, returning the current holder when it does.
+    pub fn try_acquire(&self) -> Result<Result<(), String>, Box<dyn std::error::Error>> {
+        self.locked(|| {
+            let current = self.read()?;
+            if let Some(current) = current {
+                if current.holder != self.holder && current.expires_at > now() {
+                    return Ok(Err(current.holder));
+                }
+            }
+
+            self.write(&LeaseFile {
+                holder: self.holder.clone(),
+                expires_at: now() + self.ttl.as_secs(),
+            })?;
+            Ok(Ok(()))
+        })
+    }
+
+    /// Renews the lease until it is lost, returning before it could have passed to another instance.
+    pub async fn hold(&self) {
+        let mut expires_at = now() + self.ttl.as_secs();
+        loop {
+            tokio::time::sleep(self.renew_interval()).await;
+
+            let reason = match self.try_acquire() {
+                Ok(Ok(())) => {
+                    expires_at = now() + self.ttl.as_secs();
+                    continue;
+                }
+                Ok(Err(holder)) => format!("taken over by {holder}"),
+                // keep relaying while the lease we wrote last is still valid
+                Err(err) if now() + self.renew_interval().as_secs() < expires_at => {
+                    self.report_error(err.as_ref());
+                    continue;
+                }
+                Err(err) => format!("could not be renewed: {err}"),
+            };
+
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Relayer Lease Lost
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Instance:    {}
+                            Reason:      {reason}
+                            Status:      Halted🛑
+                ",
+                self.holder
+            );
+            return;
+        }
+    }
+
+    /// Gives the lease up so a standby can take over right away.
+    pub fn release(&self) {
+        let result = self.locked(|| {
+            if self
+                .read()?
+                .is_some_and(|current| current.holder == self.holder)
+            {
+                fs::remove_file(&self.path)?;
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            self.report_error(err.as_ref());
+        }
+    }
+
+    fn renew_interval(&self) -> Duration {
+        self.ttl / 3
+    }
+
+    fn read(&self) -> Result<Option<LeaseFile>, Box<dyn std::error::Error>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes).ok()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write(&self, lease: &LeaseFile) -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(lease)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Runs `change` while holding an exclusive lock file beside the lease, so instances never race on it.
+    fn locked<T>(
+        &self,
+        change: impl FnOnce() -> Result<T, Box<dyn std::error::Error>>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let lock_path = self.path.with_extension("lock");
+        if let Err(err) = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            // a lock left behind by a crashed instance is cleared once it outlives the lease
+            let stale = fs::metadata(&lock_path)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > self.ttl);
+            if err.kind() == ErrorKind::AlreadyExists && stale {
+                fs::remove_file(&lock_path)?;
+            }
+            return Err(err.into());
+        }
+
+        let result = change();
+        fs::remove_file(&lock_path)?;
+        result
+    }
+
+    fn report_error(&self, err: &dyn std::error::Error) {
+        println!(
+            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Failed to Update Relayer Lease
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Path:        {}
+                            Status:      Failed❌
+                            Error:       {err}
+                ",
+            self.path.display()
+        );
+    }
+}
+
+/// Waits until this instance holds one of `leases`, returning its index.
+pub async fn acquire_any(leases: &[Lease]) -> usize {
+    let mut standing_by = Vec::new();
+    loop {
+        let mut holders = Vec::new();
+        for (index, lease) in leases.iter().enumerate() {
+            match lease.try_acquire() {
+                Ok(Ok(())) => {
+                    println!(
+                        "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Relayer Lease Acquired
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Instance:    {}
+                            Lease:       {}
+                            Status:      Success✅
+                ",
+                        lease.holder,
+                        lease.path.display()
+                    );
+                    return index;
+                }
+                Ok(Err(holder)) => holders.push(holder),
+                Err(err) => lease.report_error(err.as_ref()),
+            }
+        }
+
+        if !holders.is_empty() && holders != standing_by {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                              Standing By
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Instance:    {}
+                            Held By:     {}
+                            Status:      Paused⏸️
+                ",
+                leases[0].holder,
+                holders.join(", ")
+            );
+            standing_by = holders;
+        }
+        tokio::time::sleep(leases[0].renew_interval()).await;
+    }
+}
+
+/// Contents of a ledger lock file.
+#[derive(Debug, Serialize, Deserialize)]
+struct LockFile {
+    holder: String,
+    pid: u32,
+}
+
+/// An advisory lock on a file beside the ledger, so only one process writes it when no lease is configured.
+pub struct LedgerLock {
+    // the kernel releases the lock when this is closed, including when the process dies
+    _file: fs::File,
+}
+
+impl LedgerLock {
+    /// Takes the lock on the ledger at `ledger_path` for `holder`, returning the current holder when another process has it.
+    pub fn acquire(
+        ledger_path: &Path,
+        holder: &str,
+    ) -> Result<Result<Self, String>, Box<dyn std::error::Error>> {
+        let path = ledger_path.with_extension("lock");
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        if let Err(err) = file.try_lock_exclusive() {
+            if err.kind() != fs2::lock_contended_error().kind() {
+                return Err(err.into());
+            }
+            let current: Option<LockFile> = serde_json::from_slice(&fs::read(&path)?).ok();
+            return Ok(Err(current.map_or_else(
+                || "unknown".to_string(),
+                |current| format!("{} (pid {})", current.holder, current.pid),
+            )));
+        }
+
+        // the holder is only recorded for the error above, the lock itself is what excludes
+        let lock = LockFile {
+            holder: holder.to_string(),
+            pid: std::process::id(),
+        };
+        file.set_len(0)?;
+        file.write_all(&serde_json::to_vec(&lock)?)?;
+        Ok(Ok(Self { _file: file }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_ledger(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("ledger.json")
+    }
+
+    #[test]
+    fn ledger_lock_is_exclusive_until_dropped() {
+        let ledger_path = temp_ledger("ledger-lock");
+
+        let lock = LedgerLock::acquire(&ledger_path, "relayer")
+            .unwrap()
+            .unwrap();
+        let holder = LedgerLock::acquire(&ledger_path, "other")
+            .unwrap()
+            .err()
+            .unwrap();
+        assert_eq!(holder, format!("relayer (pid {})", std::process::id()));
+
+        drop(lock);
+        assert!(LedgerLock::acquire(&ledger_path, "other").unwrap().is_ok());
+
+        fs::remove_dir_all(ledger_path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn ledger_lock_left_by_a_dead_process_is_taken_over() {
+        let ledger_path = temp_ledger("ledger-lock-stale");
+
+        // a lock file from another instance whose process is gone, as after a kill or a recreated container
+        let stale = LockFile {
+            holder: "old-container".to_string(),
+            pid: u32::MAX,
+        };
+        fs::write(
+            ledger_path.with_extension("lock"),
+            serde_json::to_vec(&stale).unwrap(),
+        )
+        .unwrap();
+
+        assert!(LedgerLock::acquire(&ledger_path, "relayer")
+            .unwrap()
+            .is_ok());
+
+        fs::remove_dir_all(ledger_path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,826 @@
+use crate::limits::{Limits, Usage, DAY_SECS, HOUR_SECS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{ErrorKind, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{oneshot, Notify};
+use web3::types::{Log, H160, H256, U256};
+
+/// Where a relayed `TokensLocked` event is in the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+    /// Observed and being processed.
+    Pending,
+    /// Over a limit, waiting for manual approval.
+    Held,
+    /// Approved from the held queue, waiting to be migrated.
+    Approved,
+    /// Rejected from the held queue, waiting to be refunded.
+    Rejected,
+    /// Migrated on Solana, burn not yet confirmed.
+    Migrated,
+    /// Migrated on Solana and burn confirmed by a matching `TokensBurned` event.
+    Completed,
+    /// Returned to the user through `unlockTokens`.
+    Refunded,
+    /// Processing failed before anything was sent.
+    Failed,
+    /// Burn receipt did not carry the expected `TokensBurned` event.
+    ReconciliationFailed,
+}
+
+/// A `TokensLocked` event and everything the relayer did about it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transfer {
+    pub id: String,
+    pub eth_tx_hash: Option<H256>,
+    pub eth_user: H160,
+    pub solana_address: String,
+    pub eth_amount: U256,
+    pub amount: Option<u64>,
+    /// VOIP sent from the admin ATA, including any transfer fee.
+    #[serde(default)]
+    pub migrated_amount: Option<u64>,
+    pub status: TransferStatus,
+    pub sol_signature: Option<String>,
+    pub eth_burn_tx_hash: Option<H256>,
+    pub eth_refund_tx_hash: Option<H256>,
+    pub error: Option<String>,
+    /// Screening and manual decisions and notes on this transfer.
+    #[serde(default)]
+    pub audit: Vec<AuditEntry>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// A decision on a transfer and who made it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: u64,
+    pub actor: String,
+    pub action: Decision,
+    pub note: Option<String>,
+}
+
+/// Manual actions on the held queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Approve,
+    Reject,
+    Annotate,
+    /// Held automatically, e.g. by screening.
+    Hold,
+}
+
+impl Transfer {
+    /// A new pending transfer for the `TokensLocked` event in `log`.
+    pub fn new(log: &Log, eth_user: H160, solana_address: &str, eth_amount: U256) -> Self {
+        let now = now();
+        Self {
+            id: transfer_id(log),
+            eth_tx_hash: log.transaction_hash,
+            eth_user,
+            solana_address: solana_address.to_string(),
+            eth_amount,
+            amount: None,
+            migrated_amount: None,
+            status: TransferStatus::Pending,
+            sol_signature: None,
+            eth_burn_tx_hash: None,
+            eth_refund_tx_hash: None,
+            error: None,
+            audit: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Identifies a `TokensLocked` event by transaction hash and log index.
+pub fn transfer_id(log: &Log) -> String {
+    format!(
+        "{:?}:{}",
+        log.transaction_hash.unwrap_or_default(),
+        log.log_index.unwrap_or_default()
+    )
+}
+
+/// Seconds since the unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Chain and ledger totals when supply monitoring started, plus admin ATA outflow since.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupplyBaseline {
+    pub bridge_locked: U256,
+    pub ledger_outstanding: U256,
+    pub ledger_migrated: u64,
+    /// Admin ATA balance at the latest observation.
+    pub admin_ata_balance: u64,
+    /// VOIP sent out of the admin ATA by transactions since the baseline, top-ups aside.
+    pub admin_outflow: u64,
+    /// Newest admin ATA transaction counted in `admin_outflow`.
+    #[serde(default)]
+    pub last_signature: Option<String>,
+}
+
+// journal entries appended before the writer folds them into the snapshot
+const COMPACT_EVERY: usize = 1_000;
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct LedgerFile {
+    #[serde(default)]
+    transfers: BTreeMap<String, Transfer>,
+    #[serde(default)]
+    supply: Option<SupplyBaseline>,
+    /// First Ethereum block not yet fully processed.
+    #[serde(default)]
+    next_block: Option<u64>,
+}
+
+/// A change appended to the journal; each one replaces what it names, so replaying it twice is harmless.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+enum JournalEntry {
+    Transfer(Box<Transfer>),
+    Supply(Option<SupplyBaseline>),
+    Cursor(u64),
+}
+
+impl LedgerFile {
+    /// Reads the snapshot at `path` and replays its journal onto it.
+    fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut state: LedgerFile = match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => LedgerFile::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let journal = match fs::read(journal_path(path)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let lines: Vec<&[u8]> = journal
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .collect();
+        for (index, line) in lines.iter().enumerate() {
+            match serde_json::from_slice(line) {
+                Ok(entry) => state.apply(entry),
+                // a crash mid-append leaves the last line torn
+                Err(_) if index + 1 == lines.len() => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(state)
+    }
+
+    fn apply(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Transfer(transfer) => {
+                self.transfers.insert(transfer.id.clone(), *transfer);
+            }
+            JournalEntry::Supply(supply) => self.supply = supply,
+            JournalEntry::Cursor(block) => {
+                self.next_block = self.next_block.max(Some(block));
+            }
+        }
+    }
+
+    fn outstanding_locked(&self) -> U256 {
+        self.transfers
+            .values()
+            .filter(|transfer| {
+                matches!(
+                    transfer.status,
+                    TransferStatus::Pending
+                        | TransferStatus::Held
+                        | TransferStatus::Approved
+                        | TransferStatus::Rejected
+                        | TransferStatus::Migrated
+                        | TransferStatus::Failed
+                )
+            })
+            .fold(U256::zero(), |total, transfer| {
+                total.saturating_add(transfer.eth_amount)
+            })
+    }
+
+    /// Amount admitted for migration within the rolling windows ending `now`.
+    fn usage(&self, user: Option<H160>, now: u64) -> Usage {
+        let mut usage = Usage::default();
+        for transfer in self.transfers.values() {
+            let admitted = matches!(
+                transfer.status,
+                TransferStatus::Pending
+                    | TransferStatus::Approved
+                    | TransferStatus::Migrated
+                    | TransferStatus::Completed
+                    | TransferStatus::ReconciliationFailed
+            );
+            let amount = match transfer.amount {
+                Some(amount) if admitted => amount,
+                _ => continue,
+            };
+            if user.is_some_and(|user| user != transfer.eth_user) {
+                continue;
+            }
+
+            let age = now.saturating_sub(transfer.created_at);
+            if age < HOUR_SECS {
+                usage.hourly = usage.hourly.saturating_add(amount);
+            }
+            if age < DAY_SECS {
+                usage.daily = usage.daily.saturating_add(amount);
+            }
+        }
+        usage
+    }
+
+    fn migrated_total(&self) -> u64 {
+        self.transfers
+            .values()
+            // a pending transfer's signature is an unconfirmed migrate transaction
+            .filter(|transfer| {
+                transfer.sol_signature.is_some() && transfer.status != TransferStatus::Pending
+            })
+            .filter_map(|transfer| transfer.migrated_amount.or(transfer.amount))
+            .fold(0, u64::saturating_add)
+    }
+}
+
+/// Relayer record of every transfer, kept as a JSON snapshot plus a journal of changes since.
+pub struct Ledger {
+    path: Mutex<PathBuf>,
+    state: Mutex<LedgerFile>,
+    decided: Notify,
+    /// Queue of the thread writing changes to disk; `None` keeps changes in memory, e.g. when dry running.
+    writer: Option<mpsc::Sender<Write>>,
+}
+
+impl Ledger {
+    /// Loads the ledger at `path`, starting empty when the file does not exist; a `read_only` ledger is never written back.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        read_only: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.into();
+        let state = LedgerFile::load(&path)?;
+
+        let writer = (!read_only).then(|| {
+            let (sender, receiver) = mpsc::channel();
+            let journal = Journal {
+                path: path.clone(),
+                state: state.clone(),
+                appended: 0,
+                // fold in the journal left by the previous run on the first write
+                compact: true,
+            };
+            std::thread::spawn(move || journal.run(receiver));
+            sender
+        });
+
+        Ok(Self {
+            path: Mutex::new(path),
+            state: Mutex::new(state),
+            decided: Notify::new(),
+            writer,
+        })
+    }
+
+    /// Re-reads the ledger at `path`, picking up changes written by another instance, and writes there from now on.
+    pub fn reopen(&self, path: impl Into<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+        let path = path.into();
+        let state = LedgerFile::load(&path)?;
+        let mut current = self.state.lock().unwrap();
+        if let Some(writer) = &self.writer {
+            let _ = writer.send(Write::Reset(path.clone(), state.clone()));
+        }
+        *current = state;
+        *self.path.lock().unwrap() = path;
+        Ok(())
+    }
+
+    /// Waits until every change made so far is on disk.
+    pub async fn flush(&self) {
+        if let Some(writer) = &self.writer {
+            let (done, flushed) = oneshot::channel();
+            if writer.send(Write::Flush(done)).is_ok() {
+                let _ = flushed.await;
+            }
+        }
+    }
+
+    /// Records `transfer` unless one with the same id exists, returning the stored entry and whether it is new.
+    pub fn record(&self, transfer: Transfer) -> (Transfer, bool) {
+        let mut state = self.state.lock().unwrap();
+        let recorded = !state.transfers.contains_key(&transfer.id);
+        let stored = state
+            .transfers
+            .entry(transfer.id.clone())
+            .or_insert(transfer)
+            .clone();
+        if recorded {
+            self.persist(JournalEntry::Transfer(Box::new(stored.clone())));
+        }
+        (stored, recorded)
+    }
+
+    /// Applies `change` to the transfer `id`, if recorded.
+    pub fn update(&self, id: &str, change: impl FnOnce(&mut Transfer)) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(transfer) = state.transfers.get_mut(id) {
+            change(transfer);
+            transfer.updated_at = now();
+            self.persist(JournalEntry::Transfer(Box::new(transfer.clone())));
+        }
+    }
+
+    /// The transfer `id`, if recorded.
+    pub fn get(&self, id: &str) -> Option<Transfer> {
+        self.state.lock().unwrap().transfers.get(id).cloned()
+    }
+
+    /// Transfers currently in any of `statuses`, oldest first.
+    pub fn with_status(&self, statuses: &[TransferStatus]) -> Vec<Transfer> {
+        self.find(|transfer| statuses.contains(&transfer.status))
+    }
+
+    /// Transfers for which `matches` holds, oldest first.
+    pub fn find(&self, matches: impl Fn(&Transfer) -> bool) -> Vec<Transfer> {
+        let mut transfers: Vec<Transfer> = self
+            .state
+            .lock()
+            .unwrap()
+            .transfers
+            .values()
+            .filter(|transfer| matches(transfer))
+            .cloned()
+            .collect();
+        transfers.sort_by_key(|transfer| transfer.created_at);
+        transfers
+    }
+
+    /// Records `decision` by `actor` on transfer `id`; only held transfers can be approved or rejected.
+    pub fn decide(
+        &self,
+        id: &str,
+        decision: Decision,
+        actor: &str,
+        note: Option<String>,
+    ) -> Result<Transfer, String> {
+        let mut state = self.state.lock().unwrap();
+        let transfer = state
+            .transfers
+            .get_mut(id)
+            .ok_or_else(|| format!("transfer {id} is not recorded"))?;
+
+        match decision {
+            Decision::Approve | Decision::Reject if transfer.status != TransferStatus::Held => {
+                return Err(format!("transfer {id} is {:?}, not held", transfer.status));
+            }
+            Decision::Approve => transfer.status = TransferStatus::Approved,
+            Decision::Reject => transfer.status = TransferStatus::Rejected,
+            Decision::Annotate if note.is_none() => {
+                return Err("annotation needs a note".to_string());
+            }
+            Decision::Annotate => {}
+            Decision::Hold => return Err("transfers are only held by the relayer".to_string()),
+        }
+
+        let now = now();
+        transfer.audit.push(AuditEntry {
+            at: now,
+            actor: actor.to_string(),
+            action: decision,
+            note,
+        });
+        transfer.updated_at = now;
+
+        let transfer = transfer.clone();
+        self.persist(JournalEntry::Transfer(Box::new(transfer.clone())));
+        self.decided.notify_one();
+        Ok(transfer)
+    }
+
+    /// Waits for the next approve, reject or annotate decision.
+    pub async fn wait_decided(&self) {
+        self.decided.notified().await;
+    }
+
+    /// Admits `amount` for transfer `id` within `limits`, holding the transfer otherwise.
+    pub fn admit(&self, id: &str, amount: u64, limits: &Limits) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let user = match state.transfers.get(id) {
+            Some(transfer) => transfer.eth_user,
+            None => return Err(format!("transfer {id} is not recorded")),
+        };
+
+        // check and record under one lock so concurrent transfers cannot overshoot a window
+        let now = now();
+        let result = limits.check(
+            amount,
+            &state.usage(Some(user), now),
+            &state.usage(None, now),
+        );
+        if let Some(transfer) = state.transfers.get_mut(id) {
+            transfer.amount = Some(amount);
+            transfer.updated_at = now;
+            if let Err(err) = &result {
+                transfer.status = TransferStatus::Held;
+                transfer.error = Some(err.clone());
+                transfer.audit.push(AuditEntry {
+                    at: now,
+                    actor: "limits".to_string(),
+                    action: Decision::Hold,
+                    note: Some(err.clone()),
+                });
+            }
+            self.persist(JournalEntry::Transfer(Box::new(transfer.clone())));
+        }
+        result
+    }
+
+    /// Checks `amount` from `user` against `limits` without admitting it.
+    pub fn check_limits(&self, user: H160, amount: u64, limits: &Limits) -> Result<(), String> {
+        let state = self.state.lock().unwrap();
+        let now = now();
+        limits.check(
+            amount,
+            &state.usage(Some(user), now),
+            &state.usage(None, now),
+        )
+    }
+
+    /// Eth amount of recorded locks the bridge should still hold.
+    pub fn outstanding_locked(&self) -> U256 {
+        self.state.lock().unwrap().outstanding_locked()
+    }
+
+    /// VOIP sent from the admin ATA for recorded migrations.
+    pub fn migrated_total(&self) -> u64 {
+        self.state.lock().unwrap().migrated_total()
+    }
+
+    /// The supply baseline, if one was taken.
+    pub fn supply(&self) -> Option<SupplyBaseline> {
+        self.state.lock().unwrap().supply.clone()
+    }
+
+    /// Takes the supply baseline from the ledger's current totals unless one exists, returning the baseline in force.
+    pub fn start_supply(
+        &self,
+        bridge_locked: U256,
+        admin_ata_balance: u64,
+        last_signature: Option<String>,
+    ) -> SupplyBaseline {
+        let mut state = self.state.lock().unwrap();
+        if let Some(baseline) = &state.supply {
+            return baseline.clone();
+        }
+
+        let baseline = SupplyBaseline {
+            bridge_locked,
+            ledger_outstanding: state.outstanding_locked(),
+            ledger_migrated: state.migrated_total(),
+            admin_ata_balance,
+            admin_outflow: 0,
+            last_signature,
+        };
+        state.supply = Some(baseline.clone());
+        self.persist(JournalEntry::Supply(Some(baseline.clone())));
+        baseline
+    }
+
+    /// Adds `outflow` from admin ATA transactions up to `last_signature` to the baseline, if one exists.
+    pub fn add_supply_outflow(
+        &self,
+        outflow: u64,
+        admin_ata_balance: u64,
+        last_signature: Option<String>,
+    ) -> Option<SupplyBaseline> {
+        let mut state = self.state.lock().unwrap();
+        let baseline = state.supply.as_mut()?;
+        baseline.admin_outflow = baseline.admin_outflow.saturating_add(outflow);
+        baseline.admin_ata_balance = admin_ata_balance;
+        baseline.last_signature = last_signature;
+
+        let baseline = baseline.clone();
+        self.persist(JournalEntry::Supply(Some(baseline.clone())));
+        Some(baseline)
+    }
+
+    /// Drops the supply baseline so the next check takes a new one.
+    pub fn reset_supply(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.supply = None;
+        self.persist(JournalEntry::Supply(None));
+    }
+
+    /// First Ethereum block not yet fully processed, if any block was.
+    pub fn next_block(&self) -> Option<u64> {
+        self.state.lock().unwrap().next_block
+    }
+
+    /// Moves the block cursor forward to `block`, never back.
+    pub fn advance_cursor(&self, block: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.next_block.is_some_and(|next| next >= block) {
+            return;
+        }
+        state.next_block = Some(block);
+        self.persist(JournalEntry::Cursor(block));
+    }
+
+    /// Queues `entry` for the writer; called with the state locked so entries reach disk in order.
+    fn persist(&self, entry: JournalEntry) {
+        if let Some(writer) = &self.writer {
+            let _ = writer.send(Write::Entry(entry));
+        }
+    }
+}
+
+/// Requests to the ledger's writer thread.
+enum Write {
+    Entry(JournalEntry),
+    /// Replaces the writer's file and copy after a reload.
+    Reset(PathBuf, LedgerFile),
+    /// Answered once every earlier request is on disk.
+    Flush(oneshot::Sender<()>),
+}
+
+/// The `.journal` file beside the ledger snapshot at `path`.
+fn journal_path(path: &Path) -> PathBuf {
+    path.with_extension("journal")
+}
+
+/// Appends ledger changes off the async runtime, periodically folding them into the snapshot.
+struct Journal {
+    path: PathBuf,
+    /// Copy of the ledger with every received entry applied, written out on compaction.
+    state: LedgerFile,
+    appended: usize,
+    compact: bool,
+}
+
+impl Journal {
+    fn run(mut self, receiver: mpsc::Receiver<Write>) {
+        while let Ok(first) = receiver.recv() {
+            // write whatever queued up meanwhile in one go
+            let mut lines = Vec::new();
+            let mut flushed = Vec::new();
+            for write in std::iter::once(first).chain(receiver.try_iter()) {
+                match write {
+                    Write::Entry(entry) => {
+                        if let Ok(line) = serde_json::to_vec(&entry) {
+                            lines.push(line);
+                        }
+                        self.state.apply(entry);
+                    }
+                    Write::Reset(path, state) => {
+                        // entries queued before the reset belong to the previous file
+                        self.write_lines(std::mem::take(&mut lines));
+                        self.path = path;
+                        self.state = state;
+                        self.compact = true;
+                    }
+                    Write::Flush(done) => flushed.push(done),
+                }
+            }
+
+            self.write_lines(lines);
+            for done in flushed {
+                let _ = done.send(());
+            }
+        }
+    }
+
+    fn write_lines(&mut self, lines: Vec<Vec<u8>>) {
+        if lines.is_empty() {
+            return;
+        }
+        if let Err(err) = self.write(&lines) {
+            // the next write rewrites the snapshot with everything received
+            self.compact = true;
+            self.report_error(&err);
+        }
+    }
+
+    fn write(&mut self, lines: &[Vec<u8>]) -> std::io::Result<()> {
+        if self.compact || self.appended + lines.len() >= COMPACT_EVERY {
+            return self.compact();
+        }
+
+        let mut batch = Vec::new();
+        for line in lines {
+            batch.extend_from_slice(line);
+            batch.push(b'\n');
+        }
+        let mut journal = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal_path(&self.path))?;
+        journal.write_all(&batch)?;
+        journal.sync_data()?;
+        self.appended += lines.len();
+        Ok(())
+    }
+
+    /// Rewrites the snapshot and empties the journal.
+    fn compact(&mut self) -> std::io::Result<()> {
+        // write to a sibling file first so a crash never leaves a truncated ledger
+        let tmp_path = self.path.with_extension("json.tmp");
+        let mut snapshot = fs::File::create(&tmp_path)?;
+        snapshot.write_all(&serde_json::to_vec_pretty(&self.state)?)?;
+        snapshot.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        // a crash before truncating only replays entries the snapshot already holds
+        fs::File::create(journal_path(&self.path))?.sync_all()?;
+        self.appended = 0;
+        self.compact = false;
+        Ok(())
+    }
+
+    fn report_error(&self, err: &std::io::Error) {
+        println!(
+            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Failed to Persist Ledger
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Path:        {}
+                            Status:      Failed❌
+                            Error:       {err}
+                ",
+            self.path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::tests::lock_log;
+
+    fn transfer(user: H160, amount: u64, status: TransferStatus, created_at: u64) -> Transfer {
+        let mut log = lock_log(U256::from(amount), user, "dest");
+        log.transaction_hash = Some(H256::from_low_u64_be(created_at));
+        Transfer {
+            amount: Some(amount),
+            status,
+            created_at,
+            ..Transfer::new(&log, user, "dest", U256::from(amount))
+        }
+    }
+
+    #[test]
+    fn usage_rolls_out_of_each_window() {
+        let user = H160::repeat_byte(1);
+        let now = 10 * DAY_SECS;
+        let mut ledger = LedgerFile::default();
+        for (amount, created_at) in [
+            (1, now),
+            (10, now - HOUR_SECS + 1),
+            (100, now - HOUR_SECS),
+            (1_000, now - DAY_SECS + 1),
+            (10_000, now - DAY_SECS),
+        ] {
+            let transfer = transfer(user, amount, TransferStatus::Completed, created_at);
+            ledger.transfers.insert(transfer.id.clone(), transfer);
+        }
+
+        let usage = ledger.usage(Some(user), now);
+        assert_eq!((usage.hourly, usage.daily), (11, 1_111));
+    }
+
+    #[test]
+    fn usage_counts_only_admitted_transfers_of_the_user() {
+        let user = H160::repeat_byte(1);
+        let other = H160::repeat_byte(2);
+        let now = 10 * DAY_SECS;
+        let mut ledger = LedgerFile::default();
+        for (who, amount, status) in [
+            (user, 1, TransferStatus::Migrated),
+            (user, 10, TransferStatus::Held),
+            (user, 100, TransferStatus::Refunded),
+            (other, 1_000, TransferStatus::Pending),
+        ] {
+            let transfer = transfer(who, amount, status, now - amount);
+            ledger.transfers.insert(transfer.id.clone(), transfer);
+        }
+
+        assert_eq!(ledger.usage(Some(user), now).hourly, 1);
+        assert_eq!(ledger.usage(None, now).hourly, 1_001);
+    }
+
+    #[test]
+    fn admit_holds_transfers_over_a_limit() {
+        let ledger = Ledger::open("/nonexistent/ledger.json", true).unwrap();
+        let limits = Limits {
+            max_transfer: None,
+            user_hourly: Some(100),
+            user_daily: None,
+            global_hourly: None,
+            global_daily: None,
+        };
+        let user = H160::repeat_byte(1);
+        let first = transfer(user, 0, TransferStatus::Pending, now());
+        let second = transfer(user, 0, TransferStatus::Pending, now() + 1);
+        ledger.record(first.clone());
+        ledger.record(second.clone());
+
+        assert_eq!(ledger.admit(&first.id, 60, &limits), Ok(()));
+        assert!(ledger.admit(&second.id, 41, &limits).is_err());
+        let held = ledger.get(&second.id).unwrap();
+        assert_eq!(held.status, TransferStatus::Held);
+        let audit = held.audit.last().unwrap();
+        assert_eq!(
+            (audit.actor.as_str(), audit.action),
+            ("limits", Decision::Hold)
+        );
+        assert!(audit
+            .note
+            .as_deref()
+            .unwrap()
+            .starts_with("per-user hourly limit"));
+    }
+
+    #[tokio::test]
+    async fn journal_survives_reopening() {
+        let dir = std::env::temp_dir().join(format!("ledger-journal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ledger.json");
+        let user = H160::repeat_byte(1);
+        let first = transfer(user, 5, TransferStatus::Pending, 1);
+        let second = transfer(user, 7, TransferStatus::Pending, 2);
+
+        let ledger = Ledger::open(&path, false).unwrap();
+        ledger.record(first.clone());
+        ledger.flush().await;
+        ledger.record(second.clone());
+        ledger.update(&first.id, |transfer| {
+            transfer.status = TransferStatus::Completed
+        });
+        ledger.advance_cursor(42);
+        ledger.flush().await;
+        drop(ledger);
+
+        // the first write compacted into the snapshot, later ones only hit the journal
+        let snapshot: LedgerFile = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(snapshot.transfers.len(), 1);
+
+        // a torn final line is dropped
+        let mut journal = fs::OpenOptions::new()
+            .append(true)
+            .open(journal_path(&path))
+            .unwrap();
+        journal.write_all(b"{\"kind\":\"cursor\",\"val").unwrap();
+
+        let reopened = Ledger::open(&path, false).unwrap();
+        assert_eq!(
+            reopened.get(&first.id).unwrap().status,
+            TransferStatus::Completed
+        );
+        assert!(reopened.get(&second.id).is_some());
+        assert_eq!(reopened.next_block(), Some(42));
+        drop(reopened);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reopen_switches_to_another_ledger() {
+        let dir = std::env::temp_dir().join(format!("ledger-reopen-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (first_path, second_path) = (dir.join("ledger.0.json"), dir.join("ledger.1.json"));
+        let user = H160::repeat_byte(1);
+        let first = transfer(user, 5, TransferStatus::Pending, 1);
+        let second = transfer(user, 7, TransferStatus::Pending, 2);
+
+        let ledger = Ledger::open(&first_path, false).unwrap();
+        ledger.record(first.clone());
+        ledger.reopen(&second_path).unwrap();
+        assert!(ledger.get(&first.id).is_none());
+        ledger.record(second.clone());
+        ledger.flush().await;
+        drop(ledger);
+
+        // changes queued before the switch stay with the ledger they were made to
+        let first_ledger = Ledger::open(&first_path, true).unwrap();
+        assert!(first_ledger.get(&first.id).is_some());
+        assert!(first_ledger.get(&second.id).is_none());
+        let second_ledger = Ledger::open(&second_path, true).unwrap();
+        assert!(second_ledger.get(&first.id).is_none());
+        assert!(second_ledger.get(&second.id).is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
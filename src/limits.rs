@@ -0,0 +1,102 @@
+pub const HOUR_SECS: u64 = 60 * 60;
+pub const DAY_SECS: u64 = 24 * HOUR_SECS;
+
+/// Migration ceilings in SPL base units, unlimited when `None`.
+pub struct Limits {
+    pub max_transfer: Option<u64>,
+    pub user_hourly: Option<u64>,
+    pub user_daily: Option<u64>,
+    pub global_hourly: Option<u64>,
+    pub global_daily: Option<u64>,
+}
+
+/// Amount already admitted within each rolling window.
+#[derive(Default)]
+pub struct Usage {
+    pub hourly: u64,
+    pub daily: u64,
+}
+
+impl Limits {
+    /// Checks `amount` against every limit, returning the first one it would exceed.
+    pub fn check(&self, amount: u64, user: &Usage, global: &Usage) -> Result<(), String> {
+        let checks = [
+            ("per-transfer maximum", 0, self.max_transfer),
+            ("per-user hourly limit", user.hourly, self.user_hourly),
+            ("per-user daily limit", user.daily, self.user_daily),
+            ("global hourly limit", global.hourly, self.global_hourly),
+            ("global daily limit", global.daily, self.global_daily),
+        ];
+
+        for (name, used, limit) in checks {
+            if let Some(limit) = limit {
+                if used.saturating_add(amount) > limit {
+                    return Err(format!(
+                        "{name} exceeded: {amount} with {used} already used of {limit}"
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> Limits {
+        Limits {
+            max_transfer: Some(100),
+            user_hourly: Some(150),
+            user_daily: Some(300),
+            global_hourly: Some(1_000),
+            global_daily: Some(2_000),
+        }
+    }
+
+    fn usage(hourly: u64, daily: u64) -> Usage {
+        Usage { hourly, daily }
+    }
+
+    #[test]
+    fn admits_amounts_up_to_each_limit() {
+        let limits = limits();
+        assert_eq!(limits.check(100, &usage(0, 0), &usage(0, 0)), Ok(()));
+        assert_eq!(
+            limits.check(50, &usage(100, 250), &usage(950, 1_950)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn refuses_amounts_one_past_a_limit() {
+        let limits = limits();
+        let over = |amount, user, global| limits.check(amount, &user, &global).unwrap_err();
+
+        assert!(over(101, usage(0, 0), usage(0, 0)).starts_with("per-transfer maximum"));
+        assert!(over(51, usage(100, 100), usage(0, 0)).starts_with("per-user hourly limit"));
+        assert!(over(51, usage(0, 250), usage(0, 0)).starts_with("per-user daily limit"));
+        assert!(over(51, usage(0, 0), usage(950, 950)).starts_with("global hourly limit"));
+        assert!(over(51, usage(0, 0), usage(0, 1_950)).starts_with("global daily limit"));
+    }
+
+    #[test]
+    fn unset_limits_and_saturated_usage() {
+        let unlimited = Limits {
+            max_transfer: None,
+            user_hourly: None,
+            user_daily: None,
+            global_hourly: None,
+            global_daily: None,
+        };
+        assert_eq!(
+            unlimited.check(u64::MAX, &usage(u64::MAX, u64::MAX), &usage(0, 0)),
+            Ok(())
+        );
+        assert!(limits()
+            .check(1, &usage(0, u64::MAX), &usage(0, 0))
+            .is_err());
+    }
+}
@@ -1,29 +1,98 @@
-use anchor_client::anchor_lang::prelude::AccountMeta;
-use anchor_client::solana_client::rpc_client::RpcClient;
-use anchor_client::solana_sdk::hash::Hash;
+mod amount;
+mod balance;
+mod breaker;
+mod bridge;
+mod config;
+mod dry_run;
+mod endpoints;
+mod gate;
+mod held;
+mod lease;
+mod ledger;
+mod limits;
+mod metrics;
+mod notify;
+mod partition;
+mod polling;
+mod reconcile;
+mod relay;
+mod replay;
+mod resume;
+mod roles;
+mod screening;
+mod server;
+mod shutdown;
+mod simulation;
+mod supply;
+mod validation;
+
 use anchor_client::{
-    solana_sdk::{
-        instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer,
-        transaction::Transaction,
-    },
+    solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer},
     Client, Cluster,
 };
+use balance::BalanceGuard;
+use breaker::Breaker;
+use bridge::{
+    check_pause, monitor_pause, BRIDGE_PAUSED_REASON, OWNERSHIP_TRANSFERRED_TOPIC,
+    TOKENS_LOCKED_TOPIC,
+};
+use config::Settings;
 use dotenv::dotenv;
-use hex_literal::hex;
+use endpoints::{monitor_endpoints, monitor_subscription, solana_client, Endpoints, FailoverHttp};
+use gate::Gate;
+use held::run_held;
+use lease::{acquire_any, Lease, LedgerLock};
+use ledger::Ledger;
+use metrics::Metrics;
+use notify::{run_notify, Incident, Notifier};
+use partition::Partition;
+use polling::{monitor_heads, EventSource};
+use reconcile::run_reconcile;
+use relay::Relayer;
+use replay::Replay;
+use resume::InFlight;
+use roles::{check_roles, monitor_roles};
+use screening::Screening;
+use server::{serve, ServerState};
+use shutdown::Shutdown;
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::Mint;
 use std::env;
-use std::rc::Rc;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::Arc;
+use std::time::Duration;
+use supply::{run_supply, SupplyMonitor};
+use tokio::sync::{oneshot, Semaphore};
+use validation::DestinationValidator;
+use web3::api::SubscriptionStream;
 use web3::contract::{Contract, Options};
-use web3::ethabi::{decode, ParamType};
 use web3::futures::StreamExt;
-use web3::signing;
-use web3::types::{Address, FilterBuilder, H160, U256};
+use web3::signing::{self, Key};
+use web3::transports::WebSocket;
+use web3::types::{Address, BlockHeader, Filter, FilterBuilder, Log, H160};
 
 #[tokio::main]
 async fn main() {
     // load .env file
     dotenv().ok();
 
+    // held queue and supply commands only talk to a running relayer
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("held") {
+        run_held(&args[1..], &Settings::from_env()).await;
+        return;
+    }
+    if args.first().map(String::as_str) == Some("supply") {
+        run_supply(&args[1..], &Settings::from_env()).await;
+        return;
+    }
+    if args.first().map(String::as_str) == Some("notify") {
+        run_notify(&args[1..], &Settings::from_env().notify).await;
+        return;
+    }
+
     // verify environment variables
     let sol_admin_private_key =
         env::var("SOLANA_ADMIN_PRIVATE_KEY").expect("Failed to get SOLANA_ADMIN_PRIVATE_KEY");
@@ -46,6 +115,40 @@ async fn main() {
         env::var("SOL_VOIP_TOKEN_MINT").expect("Failed to get SOL_VOIP_TOKEN_MINT");
     let sol_voip_migration_address =
         env::var("SOL_MIGRATION_PROGRAM_ID").expect("Failed to get SOL_MIGRATION_PROGRAM_ID");
+    let settings = Settings::from_env();
+
+    // comma-separated RPC endpoints, preferred first
+    let eth_wss_endpoints = Endpoints::new(
+        "Ethereum Websocket",
+        &eth_wss_rpc_endpoint,
+        settings.eth_max_lag_blocks,
+    );
+    let eth_http_endpoints = Arc::new(Endpoints::new(
+        "Ethereum",
+        &eth_http_rpc_endpoint,
+        settings.eth_max_lag_blocks,
+    ));
+    let sol_endpoints = Arc::new(Endpoints::new(
+        "Solana",
+        &sol_rpc_endpoint,
+        settings.sol_max_lag_slots,
+    ));
+
+    // one-off commands
+    if args.first().map(String::as_str) == Some("reconcile") {
+        run_reconcile(
+            &args[1..],
+            eth_http_endpoints.clone(),
+            &eth_voip_bridge_address,
+            sol_endpoints.clone(),
+            &sol_voip_mint_address,
+            &sol_voip_migration_address,
+        )
+        .await;
+        return;
+    }
+    let replay =
+        (args.first().map(String::as_str) == Some("replay")).then(|| Replay::from_args(&args[1..]));
 
     // print logs
     println!(
@@ -108,6 +211,13 @@ async fn main() {
         ",
         sol_voip_mint_address
     );
+    if settings.dry_run {
+        println!(
+            "
+        Dry Run: transactions are simulated, nothing is broadcast
+        "
+        );
+    }
 
     println!(
         "
@@ -142,59 +252,191 @@ async fn main() {
     ",
     );
 
-    loop {
-        let _ = run_relayer(
-            &eth_wss_rpc_endpoint,
-            &eth_http_rpc_endpoint,
-            &eth_voip_bridge_address,
-            &eth_admin_private_key,
-            &sol_rpc_endpoint,
-            &sol_voip_token_mint,
-            &sol_admin_pubkey,
-            &sol_admin_private_key,
-            &sol_admin_keypair,
-            &sol_voip_migration_address,
-        )
-        .await;
+    // the configured admin address must be the one the private key signs for
+    let eth_admin_key_address = signing::SecretKeyRef::new(&eth_admin_private_key).address();
+    let eth_admin_address = H160::from_str(&eth_admin_address).expect(
+        "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Failed to parse admin address
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        ",
+    );
+    if eth_admin_key_address != eth_admin_address {
+        panic!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              ETHEREUM_ADMIN_ADDRESS Does Not Match ETHEREUM_ADMIN_PRIVATE_KEY
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Configured:  {eth_admin_address:?}
+                    Key Address: {eth_admin_key_address:?}
+                    Status:      Failed❌
+        "
+        );
     }
-}
 
-async fn run_relayer(
-    eth_wss_rpc_endpoint: &str,
-    eth_http_rpc_endpoint: &str,
-    eth_voip_bridge_address: &str,
-    eth_admin_private_key: &signing::SecretKey,
-    sol_rpc_endpoint: &str,
-    sol_voip_token_mint: &Pubkey,
-    sol_admin_pubkey: &Pubkey,
-    sol_admin_private_key: &str,
-    sol_admin_keypair: &Keypair,
-    sol_voip_migration_address: &str,
-) -> web3::contract::Result<()> {
-    // --------------------- Set up eth connections --------------------- //
-    // set up websocket transport layer
-    let wss_transport = web3::transports::WebSocket::new(eth_wss_rpc_endpoint).await;
+    // locks are split between instances by Ethereum user, one lease per partition
+    let partitions = settings.partitions.max(1);
+    if partitions > 1 && settings.leader_lease_path.is_none() {
+        panic!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              RELAYER_PARTITIONS Requires LEADER_LEASE_PATH
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        "
+        );
+    }
+    // partitions only see their own ledger, so state spanning every lock cannot be checked
+    if partitions > 1
+        && (settings.limits.global_hourly.is_some()
+            || settings.limits.global_daily.is_some()
+            || !settings.supply_check_interval.is_zero())
+    {
+        panic!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              RELAYER_PARTITIONS Requires No GLOBAL_*_LIMIT And SUPPLY_CHECK_INTERVAL_SECS=0
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        "
+        );
+    }
 
-    // set up websocket connection
-    let web3 = match wss_transport {
-        Ok(wss) => web3::Web3::new(wss),
-        Err(err) => panic!(
+    // a replay holds the lease under its own name, so it never passes for the running relayer
+    let holder = match &replay {
+        Some(_) => format!("{} (replay)", settings.instance_id),
+        None => settings.instance_id.clone(),
+    };
+    // a dry run only reads the ledger
+    let read_only = settings.dry_run || replay.as_ref().is_some_and(|replay| replay.dry_run);
+
+    // a dry run never takes a lease, so it cannot keep a live relayer standing by
+    let leases: Vec<Lease> = match settings.leader_lease_path.as_ref().filter(|_| !read_only) {
+        Some(path) => (0..partitions)
+            .map(|index| {
+                Lease::new(
+                    Partition {
+                        index,
+                        count: partitions,
+                    }
+                    .path(path),
+                    holder.clone(),
+                    settings.leader_lease_ttl,
+                )
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    // only the instance holding a partition's lease relays it
+    let partition_index = match &replay {
+        Some(replay) => {
+            // never replay alongside a running relayer
+            if let Some(lease) = leases.get(replay.partition as usize) {
+                match lease.try_acquire() {
+                    Ok(Ok(())) => {}
+                    Ok(Err(holder)) => panic!(
+                        "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Relayer Lease Held By Another Instance
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Leader:      {holder}
+                    Status:      Failed❌
+        "
+                    ),
+                    Err(err) => panic!(
+                        "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                Failed to Acquire Relayer Lease
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+                    Error:       {err}
+        "
+                    ),
+                }
+            }
+            replay.partition
+        }
+        // without a lease, a dry run follows the partition it is configured for
+        None if read_only => settings.dry_run_partition,
+        None if leases.is_empty() => 0,
+        None => acquire_any(&leases).await as u64,
+    };
+    if partition_index >= partitions {
+        panic!(
             "
-                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                  Failed to setup transport layer, Use a dedicated Ethereum Websocket URL
-                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                        Status:      Failed❌
-                                        Error:       {err}
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Partition Out Of Range
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Partition:   {partition_index}
+                    Partitions:  {partitions}
+                    Status:      Failed❌
+        "
+        );
+    }
+    let partition = Partition {
+        index: partition_index,
+        count: partitions,
+    };
+    let lease = leases.get(partition_index as usize);
+
+    // record of every relayed transfer, kept across reconnects, one per partition
+    let ledger_path = partition.path(&settings.ledger_path);
+
+    // without a lease, an exclusive lock keeps a relayer and a replay off the same ledger
+    let _ledger_lock = match (lease, read_only) {
+        (None, false) => match LedgerLock::acquire(Path::new(&ledger_path), &holder) {
+            Ok(Ok(lock)) => Some(lock),
+            Ok(Err(holder)) => panic!(
+                "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                Ledger Locked By Another Process
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Path:        {ledger_path}
+                    Held By:     {holder}
+                    Status:      Failed❌
+        "
+            ),
+            Err(err) => panic!(
+                "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Failed to Lock Ledger
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Path:        {ledger_path}
+                    Status:      Failed❌
+                    Error:       {err}
+        "
+            ),
+        },
+        _ => None,
+    };
+    let ledger = match Ledger::open(&ledger_path, read_only) {
+        Ok(ledger) => Arc::new(ledger),
+        Err(err) => panic!(
             "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Failed to Open Ledger
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Path:        {ledger_path}
+                    Status:      Failed❌
+                    Error:       {err}
+        "
         ),
     };
 
-    // set up http transport layer
-    let http_transport = web3::transports::Http::new(eth_http_rpc_endpoint);
+    // deny and allow lists, reloaded when the file changes
+    let screening = Screening::load(settings.screening_path.clone());
 
-    // set up http connection
-    let http_web3: web3::Web3<web3::transports::Http> = match http_transport {
-        Ok(http) => web3::Web3::new(http),
+    // cross-chain supply metrics, scraped from the HTTP server when enabled
+    let metrics = Arc::new(Metrics::default());
+
+    // incident webhooks, delivered in the background
+    let notifier = Notifier::new(&settings.notify);
+
+    // ethereum http transport failing over between endpoints
+    let eth_transport = match FailoverHttp::new(eth_http_endpoints.clone()) {
+        Ok(transport) => transport,
         Err(err) => panic!(
             "
                 ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
@@ -206,6 +448,190 @@ async fn run_relayer(
         ),
     };
 
+    // relay a single transaction's locks, then exit
+    if let Some(replay) = &replay {
+        let shutdown = Shutdown::default();
+        tokio::select! {
+            _ = run_relayer(
+                &eth_wss_endpoints,
+                &eth_transport,
+                &eth_voip_bridge_address,
+                &eth_admin_private_key,
+                &sol_endpoints,
+                &sol_voip_token_mint,
+                &sol_admin_pubkey,
+                &sol_admin_private_key,
+                &sol_admin_keypair,
+                &sol_voip_migration_address,
+                &settings,
+                &ledger,
+                &metrics,
+                &screening,
+                &notifier,
+                partition,
+                Some(replay),
+                &shutdown,
+            ) => {}
+            _ = notifier.run() => {}
+        }
+        ledger.flush().await;
+        if let Some(lease) = lease {
+            lease.release();
+        }
+        return;
+    }
+
+    let relaying = async {
+        let mut reconnects = std::collections::VecDeque::new();
+        let mut leading = true;
+        let mut partition = partition;
+        let mut lease = lease;
+        loop {
+            if !leases.is_empty() {
+                // keep the partition while its lease is ours, otherwise take over whichever is free
+                let index = match lease.map(Lease::try_acquire) {
+                    Some(Ok(Ok(()))) if leading => partition.index,
+                    _ => acquire_any(&leases).await as u64,
+                };
+
+                // a new leader picks up what the previous one recorded
+                if !leading || index != partition.index {
+                    partition.index = index;
+                    lease = leases.get(index as usize);
+                    let ledger_path = partition.path(&settings.ledger_path);
+                    if let Err(err) = ledger.reopen(&ledger_path) {
+                        panic!(
+                            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Failed to Open Ledger
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Path:        {ledger_path}
+                    Status:      Failed❌
+                    Error:       {err}
+        "
+                        )
+                    }
+                }
+                leading = true;
+            }
+
+            let shutdown = Shutdown::default();
+            let relaying = run_relayer(
+                &eth_wss_endpoints,
+                &eth_transport,
+                &eth_voip_bridge_address,
+                &eth_admin_private_key,
+                &sol_endpoints,
+                &sol_voip_token_mint,
+                &sol_admin_pubkey,
+                &sol_admin_private_key,
+                &sol_admin_keypair,
+                &sol_voip_migration_address,
+                &settings,
+                &ledger,
+                &metrics,
+                &screening,
+                &notifier,
+                partition,
+                None,
+                &shutdown,
+            );
+
+            // stop taking events as soon as the lease is lost, stepping down once in-flight transfers finish
+            let result = match lease {
+                Some(lease) => {
+                    tokio::pin!(relaying);
+                    tokio::select! {
+                        result = &mut relaying => result,
+                        _ = lease.hold() => {
+                            shutdown.request();
+                            let _ = relaying.await;
+
+                            // the next leader reloads the ledger, so leave nothing queued
+                            ledger.flush().await;
+                            leading = false;
+                            Ok(())
+                        }
+                    }
+                }
+                None => relaying.await,
+            };
+
+            // give a failed endpoint a moment before reconnecting
+            if result.is_err() {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+
+            // alert when the connection keeps dropping
+            let now = std::time::Instant::now();
+            reconnects.push_back(now);
+            while reconnects
+                .front()
+                .is_some_and(|at| now.duration_since(*at) > settings.reconnect_storm_window)
+            {
+                reconnects.pop_front();
+            }
+            if reconnects.len() >= settings.reconnect_storm_count {
+                notifier.notify(Incident {
+                    kind: "reconnect_storm",
+                    subject: "relayer".to_string(),
+                    title: "Relayer reconnect storm".to_string(),
+                    detail: format!(
+                        "{} reconnects in the last {:?}",
+                        reconnects.len(),
+                        settings.reconnect_storm_window
+                    ),
+                });
+            }
+        }
+    };
+
+    // rank endpoints by how far they trail the others
+    let monitoring = monitor_endpoints(
+        &eth_transport,
+        &sol_endpoints,
+        settings.endpoint_health_interval,
+    );
+
+    match settings.http_addr {
+        Some(addr) => {
+            let state = Arc::new(ServerState {
+                metrics: metrics.clone(),
+                ledger: ledger.clone(),
+                admin_tokens: settings.admin_tokens.clone(),
+            });
+            tokio::join!(relaying, serve(addr, state), notifier.run(), monitoring);
+        }
+        None => {
+            tokio::join!(relaying, notifier.run(), monitoring);
+        }
+    }
+}
+
+async fn run_relayer(
+    eth_wss_endpoints: &Endpoints,
+    eth_transport: &FailoverHttp,
+    eth_voip_bridge_address: &str,
+    eth_admin_private_key: &signing::SecretKey,
+    sol_endpoints: &Arc<Endpoints>,
+    sol_voip_token_mint: &Pubkey,
+    sol_admin_pubkey: &Pubkey,
+    sol_admin_private_key: &str,
+    sol_admin_keypair: &Keypair,
+    sol_voip_migration_address: &str,
+    settings: &Settings,
+    ledger: &Ledger,
+    metrics: &Metrics,
+    screening: &Screening,
+    notifier: &Notifier<'_>,
+    partition: Partition,
+    replay: Option<&Replay>,
+    shutdown: &Shutdown,
+) -> web3::contract::Result<()> {
+    // --------------------- Set up eth connections --------------------- //
+    // set up http connection
+    let http_web3 = web3::Web3::new(eth_transport.clone());
+
     // create eth bridge contract instance
     let contract_address = eth_voip_bridge_address.parse();
     let contract = match contract_address {
@@ -225,13 +651,26 @@ async fn run_relayer(
         ),
     };
 
+    // read VOIP ERC20 decimals through the bridge's token
+    let eth_voip_token_address: Address = contract
+        .query("voipToken", (), None, Options::default(), None)
+        .await?;
+    let eth_voip_token = Contract::from_json(
+        http_web3.eth(),
+        eth_voip_token_address,
+        include_bytes!("../artifacts/eth/token/token.json"),
+    )?;
+    let eth_decimals: u8 = eth_voip_token
+        .query("decimals", (), None, Options::default(), None)
+        .await?;
+
     // --------------------- Set up sol connections --------------------- //
-    // Create sol rpc connection
-    let connection = RpcClient::new(sol_rpc_endpoint);
+    // Create nonblocking sol rpc connection
+    let connection = solana_client(sol_endpoints.clone());
 
     // Create client
     let payer = Keypair::from_base58_string(sol_admin_private_key);
-    let cluster = Cluster::from_str(sol_rpc_endpoint).expect(
+    let cluster = Cluster::from_str(&sol_endpoints.urls[0]).expect(
         "
     
             ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
@@ -240,7 +679,7 @@ async fn run_relayer(
                     Status:      Failed❌
     ",
     );
-    let client = Client::new(cluster, Rc::new(payer));
+    let client = Client::new(cluster, Arc::new(payer));
 
     // Create program
     let voip_migration_program_id = Pubkey::from_str(sol_voip_migration_address).expect(
@@ -264,14 +703,57 @@ async fn run_relayer(
 
     // --------------------- Set up sol constants --------------------- //
     // solana token program id
-    let token_program_id = Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").expect(
-        "
+    let spl_token_program_id = Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")
+        .expect(
+            "
             ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
                Failed to parse token program id
             ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
                     Status:      Failed❌
         ",
-    );
+        );
+
+    // fetch VOIP mint account
+    let mint_account = match connection.get_account(sol_voip_token_mint).await {
+        Ok(mint_account) => mint_account,
+        Err(err) => panic!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                  Failed to Fetch Solana VOIP Mint
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                        Status:      Failed❌
+                                        Error:       {err}
+            "
+        ),
+    };
+
+    // token program owning the VOIP mint, either SPL Token or Token-2022
+    let token_program_id = mint_account.owner;
+    if token_program_id != spl_token_program_id && token_program_id != spl_token_2022::ID {
+        panic!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Solana VOIP Mint Is Not Owned By A Token Program
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                        Status:      Failed❌
+                                        Owner:       {token_program_id}
+            "
+        );
+    }
+
+    // VOIP SPL decimals
+    let sol_decimals = match StateWithExtensions::<Mint>::unpack(&mint_account.data) {
+        Ok(mint) => mint.base.decimals,
+        Err(err) => panic!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                  Failed to Decode Solana VOIP Mint
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                        Status:      Failed❌
+                                        Error:       {err}
+            "
+        ),
+    };
 
     // solana system program id
     let system_program_id = Pubkey::from_str("11111111111111111111111111111111").expect(
@@ -307,486 +789,437 @@ async fn run_relayer(
         &associated_token_program_id,
     );
 
-    // decimal 10**9
-    let decimals = U256::from(1_000_000_000);
+    // reject destinations that are programs, sysvars or relayer accounts
+    let destination_validator = DestinationValidator::new(
+        [
+            system_program_id,
+            spl_token_program_id,
+            spl_token_2022::ID,
+            associated_token_program_id,
+            voip_migration_program_id,
+            *sol_voip_token_mint,
+            state_pda,
+            *sol_admin_pubkey,
+            admin_ata,
+        ],
+        settings.sol_off_curve_allowlist.iter().copied(),
+    );
+
+    // --------------------- Set up processing guards --------------------- //
+    // halt processing while admin balances are below thresholds
+    let gate = Gate::new();
+    let eth = http_web3.eth();
+    let balance_guard = BalanceGuard {
+        admin_ata,
+        sol_admin: *sol_admin_pubkey,
+        eth_admin: signing::SecretKeyRef::new(eth_admin_private_key).address(),
+        thresholds: &settings.balance_thresholds,
+        notifier,
+        reserved: AtomicU64::new(0),
+    };
+    balance_guard.check(&gate, &connection, &eth).await;
+
+    // halt processing while the bridge is paused
+    check_pause(&gate, &contract).await;
+
+    // halt processing when bridge or admin ATA balances drift from the ledger
+    let supply_monitor = SupplyMonitor {
+        eth_voip_token: eth_voip_token.clone(),
+        bridge: contract.address(),
+        admin_ata,
+        sol_admin: *sol_admin_pubkey,
+        sol_voip_token_mint: *sol_voip_token_mint,
+        ledger,
+        metrics,
+        lock_tolerance: settings.supply_lock_drift_tolerance,
+        migration_tolerance: settings.supply_migration_drift_tolerance,
+        drifted: AtomicBool::new(false),
+    };
+
+    // disabled with a zero interval, as partitioned relayers must
+    let check_supply = !settings.supply_check_interval.is_zero();
+    if check_supply {
+        supply_monitor.check(&gate, &connection).await;
+    }
+
+    // fail fast unless the relayer keys hold the bridge relayer and migration admin roles
+    let eth_relayer = balance_guard.eth_admin;
+    match check_roles(
+        &contract,
+        &program,
+        &state_pda,
+        eth_relayer,
+        *sol_admin_pubkey,
+    )
+    .await
+    {
+        Ok(roles) if roles.eth_ok() && roles.sol_ok() => {
+            println!(
+                "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Relayer Roles Verified
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Bridge Relayer:   {:?}
+                    Bridge Owner:     {:?}
+                    Migration Admin:  {}
+                    Status:           Success✅
+            ",
+                roles.bridge_relayer, roles.bridge_owner, roles.state_admin
+            );
+        }
+        Ok(roles) => panic!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Relayer Keys Do Not Hold The Bridge Or Migration Roles
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                ETH Key Address:  {:?}
+                                Bridge Relayer:   {:?}
+                                Bridge Owner:     {:?}
+                                SOL Admin:        {}
+                                Migration Admin:  {}
+                                Status:           Failed❌
+            ",
+            roles.eth_relayer,
+            roles.bridge_relayer,
+            roles.bridge_owner,
+            roles.sol_admin,
+            roles.state_admin
+        ),
+        Err(err) => panic!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                    Failed to Verify Relayer Roles
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                        Status:      Failed❌
+                                        Error:       {err}
+            "
+        ),
+    }
 
-    // --------------------- Set up TokensLocked event filter --------------------- //
-    // filter TokensLocked event
+    // --------------------- Set up bridge event filter --------------------- //
+    // filter TokensLocked and governance events
     let filter = FilterBuilder::default()
         .address(vec![Address::from_str(eth_voip_bridge_address).unwrap()])
         .topics(
-            // this is 'TokensLocked(uint256 amount, address indexed user, string solanaAddress, uint256 timestamp)' event
-            // use https://emn178.github.io/online-tools/keccak_256.html,
-            // and type in 'TokensLocked(uint256,address,string,uint256)' (without the quote)
-            // it will return result hash as used in next line
-            Some(vec![hex!(
-                "a3c29410d4173cda5ec6e52fca2d334b67df70664e718bee6d216e089b408442"
-            )
-            .into()]),
+            Some(vec![
+                TOKENS_LOCKED_TOPIC.into(),
+                OWNERSHIP_TRANSFERRED_TOPIC.into(),
+            ]),
             None,
             None,
             None,
-        )
-        .build();
-
-    // --------------------- Subscribe to TokensLocked event --------------------- //
-    // subscribe to event
-    let subs = web3.eth_subscribe().subscribe_logs(filter).await?;
+        );
 
     // --------------------- Orchestrate bridging for each event --------------------- //
-    subs.for_each_concurrent(20, |log| {
-        println!(
-            "
-            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                  Processing New Migration
-            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                    Status:      Processing ♻️
-        "
-        );
+    // stop sending on a leg after repeated failures, probing it for recovery
+    let sol_breaker = Breaker::new("solana migrate", &settings.breaker, notifier);
+    let eth_breaker = Breaker::new("ethereum burn", &settings.breaker, notifier);
+    let relayer = Relayer {
+        contract: contract.clone(),
+        eth_transport: eth_transport.clone(),
+        partition,
+        in_flight: InFlight::default(),
+        lock_permits: Semaphore::new(20),
+        shutdown,
+        program: &program,
+        connection: &connection,
+        ledger,
+        gate: &gate,
+        balance_guard: &balance_guard,
+        destination_validator: &destination_validator,
+        screening,
+        sol_breaker: &sol_breaker,
+        eth_breaker: &eth_breaker,
+        notifier,
+        settings,
+        eth_admin_private_key,
+        eth_relayer,
+        eth_voip_bridge_address: Address::from_str(eth_voip_bridge_address).unwrap(),
+        eth_decimals,
+        sol_decimals,
+        sol_voip_token_mint: *sol_voip_token_mint,
+        voip_migration_program_id,
+        sol_admin_pubkey: *sol_admin_pubkey,
+        sol_admin_keypair,
+        state_pda,
+        admin_ata,
+        token_program_id,
+        associated_token_program_id,
+        system_program_id,
+    };
 
-        let contract = contract.clone();
-        let program = &program;
-        let connection = &connection;
-        async move {
-            match log.clone() {
-                Ok(log) => {
-                    let decoded_amount = decode(&[ParamType::Uint(256)], &log.topics[1].as_bytes());
-
-                    let mut amount = 0u128;
-                    match decoded_amount {
-                        Ok(amount_vec) => {
-                            let amount_uint = amount_vec[0].clone().into_uint();
-                            match amount_uint {
-                                Some(amount_u) => {
-                                    let amount_u256 = amount_u * decimals;
-                                    amount = amount_u256.as_u128();
-                                }
-                                None => {
-                                    println!(
-                                        "
-                                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                        Decoding Failed: Invalid Amount Provided
-                                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                Status:      Failed❌
-                                    "
-                                    );
-                                }
-                            };
-                        }
-                        Err(err) => {
-                            println!(
-                                "
-                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                    Failed to parse amount from event
-                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                        Status:      Failed❌
-                                        Error:       {err}
-                            "
-                            )
-                        }
-                    };
-
-                    let decoded_eth_address =
-                        decode(&[ParamType::Address], &log.topics[2].as_bytes());
-
-                    let mut eth_address = H160::zero();
-                    match decoded_eth_address {
-                        Ok(eth_address_vec) => {
-                            let eth_address_addr = eth_address_vec[0].clone().into_address();
-                            match eth_address_addr {
-                                Some(address) => eth_address = address,
-                                None => {
-                                    println!(
-                                        "
-                                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                     Decoding Failed: Invalid Eth Address Provided
-                                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                Status:      Failed❌
-                                    "
-                                    );
-                                }
-                            }
-                        }
-                        Err(err) => {
-                            println!(
-                                "
-                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                    Failed to parse eth address from event
-                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                        Status:      Failed❌
-                                        Error:       {err}
-                            "
-                            )
-                        }
-                    }
+    // replay a single transaction instead of subscribing, keeping the gate current meanwhile
+    if let Some(replay) = replay {
+        let mut replayed = Ok(());
+        tokio::select! {
+            result = relayer.replay(&eth, replay) => replayed = result,
+            _ = balance_guard.monitor(&gate, &connection, &eth, settings.balance_check_interval) => {}
+            _ = monitor_pause(&gate, &contract, settings.bridge_pause_poll_interval) => {}
+            _ = monitor_roles(
+                &gate,
+                &contract,
+                &program,
+                &state_pda,
+                eth_relayer,
+                *sol_admin_pubkey,
+                settings.role_check_interval,
+            ) => {}
+            _ = supply_monitor.monitor(&gate, &connection, settings.supply_check_interval), if check_supply => {}
+        }
+        if let Err(err) = replayed {
+            println!(
+                "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Failed to Replay Transaction
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Tx Hash:     {:?}
+                        Status:      Failed❌
+                        Error:       {err}
+            ",
+                replay.tx_hash
+            );
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-                    let decoded_solana_address = decode(&[ParamType::String], &log.data.0);
-                    let mut solana_address = Pubkey::default();
-
-                    match decoded_solana_address {
-                        Ok(solana_address_vec) => {
-                            let solana_address_str_op = solana_address_vec[0].clone().into_string();
-                            match solana_address_str_op {
-                                Some(address_str) => {
-                                    let solana_address_pk = Pubkey::from_str(&address_str);
-                                    match solana_address_pk {
-                                        Ok(solana_address_pubkey) => {
-                                            solana_address = solana_address_pubkey;
-                                        }
-                                        Err(err) => {
-                                            println!(
-                                                "
-                                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                Decoding Failed: Failed to parse Sol Address
-                                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                        Status:      Failed❌
-                                                        Error:       {err}
-                                            "
-                                            )
-                                        }
-                                    }
-                                }
-                                None => {
-                                    println!(
-                                        "
-                                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                        Decoding Failed: Invalid Sol Address Provided
-                                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                Status:      Failed❌
-                                    "
-                                    )
-                                }
-                            }
-                        }
-                        Err(err) => {
-                            println!(
-                                "
-                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                    Failed to parse sol address from event
-                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                        Status:      Failed❌
-                                        Error:       {err}
-                            "
-                            )
-                        }
-                    }
+    // subscribe over websocket, or poll over http when configured or while the websocket is unavailable
+    let subscription = match settings.event_source {
+        EventSource::Polling => None,
+        EventSource::Websocket => Some(subscribe(eth_wss_endpoints, filter.clone().build()).await?),
+        EventSource::Auto => subscribe(eth_wss_endpoints, filter.clone().build())
+            .await
+            .ok(),
+    };
 
-                    // assert decoding pass before proceeding
-                    if amount != 0u128
-                        && eth_address != H160::zero()
-                        && solana_address != Pubkey::default()
-                    {
-                        // migrate token
-                        let sol_migration_hash = migrate(
-                            connection,
-                            &program,
-                            &state_pda,
-                            sol_voip_token_mint,
-                            &voip_migration_program_id,
-                            sol_admin_pubkey,
-                            sol_admin_keypair,
-                            &admin_ata,
-                            &solana_address,
-                            &token_program_id,
-                            &associated_token_program_id,
-                            &system_program_id,
-                            &amount,
-                        )
-                        .await;
-
-                        match sol_migration_hash {
-                            Ok(hash) => match hash {
-                                Ok(signature) => {
-                                    // burn VOIP tokens on ethereum
-                                    let eth_burn_receipt = burn(
-                                        &eth_admin_private_key,
-                                        &contract,
-                                        &eth_address,
-                                        &solana_address,
-                                    )
-                                    .await;
-
-                                    println!(
-                                        "
-                                            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                            Successfully migrated SOL VOIP Token
-                                            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                Eth Address:  {eth_address}
-                                                SOL Address:  {solana_address}
-                                                Tx Hash:      {signature}
-                                                TX Status:    Success✅
-                                        "
-                                    );
-                                    match eth_burn_receipt {
-                                        Ok(receipt_rs) => match receipt_rs {
-                                            Ok(receipt) => {
-                                                let receipt_hash =
-                                                    receipt.transaction_hash.to_string();
-                                                println!(
-                                                    "
-                                                            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                            Successfully burnt ETH VOIP Token
-                                                            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                                ETH Address:  {eth_address}
-                                                                Sol Address:  {solana_address}
-                                                                Tx Hash:      {receipt_hash}
-                                                                TX Status:    Success✅
-
-                                                        "
-                                                );
-                                                println!(
-                                                    "
-                                                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                          Processed New Migration 💥
-                                                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                            ETH Address:  {eth_address}
-                                                            Sol Address:  {solana_address}
-                                                            Amount:       {amount}
-                                                            Sol Tx Hash:  {signature}
-                                                            Eth Tx Hash:  {receipt_hash}
-                                                            Status:       Success✅
-                                                "
-                                                );
-                                            }
-                                            Err(err) => {
-                                                println!(
-                                                    "
-                                                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                            Failed to Burn ETH VOIP tokens
-                                                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                            ETH Address: {eth_address}
-                                                            Sol Address: {solana_address}
-                                                            Status:      Failed❌
-                                                            Error:       {err}
-                                                    "
-                                                )
-                                            }
-                                        },
-                                        Err(err) => {
-                                            println!(
-                                                "
-                                                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                        Failed to Burn ETH VOIP tokens
-                                                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                        ETH Address: {eth_address}
-                                                        Sol Address: {solana_address}
-                                                        Status:      Failed❌
-                                                        Error:       {err}
-                                                "
-                                            )
+    let events = async {
+        match subscription {
+            Some(subscription) => {
+                // take every delivered log at once, so each is recorded before the heads move the cursor past it
+                let (stop, stopped) = oneshot::channel::<()>();
+                let processing =
+                    subscription
+                        .logs
+                        .take_until(stopped)
+                        .for_each_concurrent(None, |log| {
+                            let relayer = &relayer;
+                            async move {
+                                match log {
+                                    Ok(log) => {
+                                        let block = log.block_number;
+                                        relayer.process_log(log).await;
+
+                                        // later logs of the same block may still arrive
+                                        if let Some(block) = block {
+                                            ledger.advance_cursor(block.as_u64());
                                         }
                                     }
-                                }
-                                Err(err) => {
-                                    println!(
-                                        "
-                                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                  Failed to Migrate SOL VOIP Tokens
-                                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                                    Eth Address: {eth_address}
-                                                    Sol Address: {solana_address}
-                                                    Status:      Failed❌
-                                                    Error Type:  Client Error
-                                                    Error:       {err}
-                                                "
-                                    )
-                                }
-                            },
-                            Err(err) => {
-                                println!(
-                                    "
-                                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                            Failed to Migrate SOL VOIP Tokens
-                                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                            Eth Address: {eth_address}
-                                            Sol Address: {solana_address}
-                                            Status:      Failed❌
-                                            Error:       {err}
-                                        "
-                                )
-                            }
-                        }
-                    } else {
-                        println!(
-                            "
-                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                        Failed to Decode Event
-                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                                        Status:      Failed❌
-                                "
-                        )
-                    }
-                }
-                Err(err) => {
-                    println!(
-                        "
+                                    Err(err) => {
+                                        println!(
+                                            "
                         ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
                                 Failed to Read Log
                         ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
                                 Status:      Failed❌
                                 Error:       {err}
                     "
-                    )
-                }
-            }
-        }
-    })
-    .await;
-
-    Ok(())
-}
-
-async fn migrate(
-    connection: &RpcClient,
-    program: &anchor_client::Program<Rc<Keypair>>,
-    state_pda: &Pubkey,
-    sol_voip_token_mint: &Pubkey,
-    sol_voip_migration_program_id: &Pubkey,
-    sol_admin_pubkey: &Pubkey,
-    sol_admin_keypair: &Keypair,
-    sol_admin_ata: &Pubkey,
-    solana_address: &Pubkey,
-    token_program_id: &Pubkey,
-    associated_token_program_id: &Pubkey,
-    system_program_id: &Pubkey,
-    amount: &u128,
-) -> Result<
-    Result<anchor_client::solana_sdk::signature::Signature, anchor_client::ClientError>,
-    Box<dyn std::error::Error>,
-> {
-    // --------------------- set up ATAs --------------------- //
-    // admin ata
-    let admin_ata = sol_admin_ata;
-
-    // derive destination ata
-    let (destination_ata, _) = Pubkey::find_program_address(
-        &[
-            &solana_address.as_ref(),
-            &token_program_id.as_ref(),
-            &sol_voip_token_mint.as_ref(),
-        ],
-        &associated_token_program_id,
-    );
+                                        )
+                                    }
+                                }
+                            }
+                        });
 
-    // check if derived account has been created
-    let destination_account = connection.get_account(&destination_ata);
-
-    // create destination ATA if it does not exist
-    if destination_account.is_err() {
-        // get recent block hash
-        let recent_blockhash = connection.get_latest_blockhash();
-        let mut latest_blockhash = Hash::default();
-        match recent_blockhash {
-            Ok(hash) => {
-                latest_blockhash = hash;
-            }
-            Err(err) => {
-                println!(
-                    "
-                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                            Failed to Get Recent Hash
-                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                            Status:      Failed❌
-                            Error:       {err}
-                "
-                )
-            }
-        }
+                // pick up locks emitted while disconnected alongside live ones
+                let processing = async {
+                    tokio::join!(processing, relayer.backfill(&eth, &filter, &settings.poll));
+                };
+                tokio::pin!(processing);
 
-        // construct create destination ATA instruction
-        let create_destination_ata_ix = Instruction {
-            program_id: associated_token_program_id.clone(),
-            accounts: vec![
-                AccountMeta::new(sol_admin_pubkey.clone(), true),
-                AccountMeta::new(destination_ata, false),
-                AccountMeta::new_readonly(solana_address.clone(), false),
-                AccountMeta::new_readonly(sol_voip_token_mint.clone(), false),
-                AccountMeta::new_readonly(system_program_id.clone(), false),
-                AccountMeta::new_readonly(token_program_id.clone(), false),
-            ],
-            data: vec![0],
-        };
-
-        // create create destination ATA transaction
-        let mut transaction =
-            Transaction::new_with_payer(&[create_destination_ata_ix], Some(&sol_admin_pubkey));
-
-        // sign create destination ATA transaction
-        transaction.sign(&[&sol_admin_keypair], latest_blockhash);
-
-        // send and confirm transaction
-        match connection.send_and_confirm_transaction(&transaction) {
-            Ok(signature) => {
-                println!(
-                    "
-                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                        Created associated token account
-                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                            Status:      Success✅
-                            Tx Hash:     {signature}
-                    ",
-                );
+                // reconnect when the websocket stalls, stops answering or falls behind
+                let reconnect = async {
+                    tokio::select! {
+                        _ = monitor_heads(
+                            subscription.heads,
+                            &eth,
+                            ledger,
+                            eth_wss_endpoints,
+                            subscription.index,
+                            settings.heads_stall_timeout,
+                        ) => {}
+                        _ = monitor_subscription(
+                            &subscription.eth,
+                            eth_wss_endpoints,
+                            subscription.index,
+                            eth_transport.endpoints(),
+                            settings.endpoint_health_interval,
+                        ) => {}
+                        _ = shutdown.requested() => {}
+                    }
+                };
+                tokio::select! {
+                    _ = &mut processing => {}
+                    _ = reconnect => {
+                        // finish the locks already taken rather than drop them mid-transfer
+                        let _ = stop.send(());
+                        processing.await;
+                    }
+                }
             }
-            Err(err) => {
+            None => {
                 println!(
                     "
-                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                    Failed to create associated token account
-                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
-                            Status:      Failed❌
-                            Error:       {err}
-                "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Polling Ethereum For Events
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        From Block:  {}
+                        Status:      Processing ♻️
+            ",
+                    settings
+                        .poll
+                        .first_block(ledger)
+                        .map_or("chain head".to_string(), |block| block.to_string())
                 );
+
+                // auto mode goes back to the websocket once it has had time to recover
+                let retry_websocket = settings.event_source == EventSource::Auto;
+                let (stop, stopped) = oneshot::channel::<()>();
+                let polling = relayer.poll_logs(&eth, &filter, &settings.poll, stopped);
+                tokio::pin!(polling);
+                let switch = async {
+                    tokio::select! {
+                        _ = tokio::time::sleep(settings.websocket_retry_interval), if retry_websocket => {}
+                        _ = shutdown.requested() => {}
+                    }
+                };
+                tokio::select! {
+                    _ = &mut polling => {}
+                    _ = switch => {
+                        // finish the batch being processed before switching back or stepping down
+                        let _ = stop.send(());
+                        polling.await;
+                    }
+                }
             }
         }
+    };
+
+    // once events stop, decisions and resumed transfers finish what they started too
+    let relaying = async {
+        tokio::join!(
+            async {
+                events.await;
+                shutdown.request();
+            },
+            relayer.process_decisions(),
+            relayer.resume_transfers(),
+        )
+    };
+
+    // process events while monitoring balances, bridge pause state, roles and supply
+    let breakers = [&sol_breaker, &eth_breaker];
+    tokio::select! {
+        _ = relaying => {}
+        _ = balance_guard.monitor(&gate, &connection, &eth, settings.balance_check_interval) => {}
+        _ = monitor_pause(&gate, &contract, settings.bridge_pause_poll_interval) => {}
+        _ = monitor_roles(
+            &gate,
+            &contract,
+            &program,
+            &state_pda,
+            eth_relayer,
+            *sol_admin_pubkey,
+            settings.role_check_interval,
+        ) => {}
+        _ = supply_monitor.monitor(&gate, &connection, settings.supply_check_interval), if check_supply => {}
+        _ = report_health(&gate, &breakers, settings.health_log_interval) => {}
     }
 
-    // get migration PDA
-    let (migration_pda, _) = Pubkey::find_program_address(
-        &[&b"migration"[..], &solana_address.as_ref()],
-        &sol_voip_migration_program_id,
-    );
+    Ok(())
+}
 
-    // call migrate function
-    let migrate_transaction_hash = program
-        .request()
-        .accounts(voip_migration::accounts::Migrate {
-            migration: migration_pda,
-            state: state_pda.clone(),
-            destination_ata,
-            admin_ata: admin_ata.clone(),
-            admin: sol_admin_pubkey.clone(),
-            destination: solana_address.clone(),
-            mint: sol_voip_token_mint.clone(),
-            token_program: token_program_id.clone(),
-            system_program: system_program_id.clone(),
-            associated_token_program: associated_token_program_id.clone(),
-        })
-        .args(voip_migration::instruction::Migrate {
-            amount: *amount as u64,
+/// Bridge log and block head subscriptions on one websocket endpoint.
+struct Subscription {
+    logs: SubscriptionStream<WebSocket, Log>,
+    heads: SubscriptionStream<WebSocket, BlockHeader>,
+    eth: web3::api::Eth<WebSocket>,
+    /// Endpoint subscribed to.
+    index: usize,
+}
+
+/// Subscribes to `filter` and new heads on the healthiest websocket endpoint.
+async fn subscribe(eth_wss_endpoints: &Endpoints, filter: Filter) -> web3::Result<Subscription> {
+    let wss_index = eth_wss_endpoints.best();
+    let subscription = async {
+        let wss = WebSocket::new(&eth_wss_endpoints.urls[wss_index]).await?;
+        let web3 = web3::Web3::new(wss);
+        let logs = web3.eth_subscribe().subscribe_logs(filter).await?;
+        let heads = web3.eth_subscribe().subscribe_new_heads().await?;
+        Ok(Subscription {
+            logs,
+            heads,
+            eth: web3.eth(),
+            index: wss_index,
         })
-        .signer(&sol_admin_keypair)
-        .send()
-        .await;
+    };
 
-    Ok(migrate_transaction_hash)
+    // the next attempt goes to another endpoint
+    match subscription.await {
+        Ok(subscription) => {
+            eth_wss_endpoints.record(wss_index, true);
+            Ok(subscription)
+        }
+        Err(err) => {
+            eth_wss_endpoints.record(wss_index, false);
+            println!(
+                "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Failed to setup transport layer, Use a dedicated Ethereum Websocket URL
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                        Endpoint:    {}
+                                        Status:      Failed❌
+                                        Error:       {err}
+            ",
+                eth_wss_endpoints.label(wss_index)
+            );
+            Err(err)
+        }
+    }
 }
 
-async fn burn(
-    eth_admin_private_key: &signing::SecretKey,
-    contract: &Contract<web3::transports::Http>,
-    ethereum_address: &H160,
-    solana_address: &Pubkey,
-) -> Result<Result<web3::types::TransactionReceipt, web3::Error>, Box<dyn std::error::Error>> {
-    let burn_transaction_receipt = contract
-        .signed_call_with_confirmations(
-            "burnTokens",
-            (ethereum_address.clone(), solana_address.to_string()),
-            Options::default(),
-            1,
-            eth_admin_private_key,
-        )
-        .await;
+async fn report_health(gate: &Gate, breakers: &[&Breaker<'_>], interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
 
-    Ok(burn_transaction_receipt)
+        let mut reasons = gate.reasons();
+        reasons.extend(
+            breakers
+                .iter()
+                .filter(|breaker| breaker.is_open())
+                .map(|breaker| format!("{} circuit breaker open", breaker.leg)),
+        );
+        let bridge_paused = gate.is_closed_for(BRIDGE_PAUSED_REASON);
+        if reasons.is_empty() {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                Relayer Health
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Bridge Paused: {bridge_paused}
+                            Status:        Running✅
+                "
+            );
+        } else {
+            let reasons = reasons.join(", ");
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                Relayer Health
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Bridge Paused: {bridge_paused}
+                            Status:        Paused⏸️
+                            Halted By:     {reasons}
+                "
+            );
+        }
+    }
 }
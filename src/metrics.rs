@@ -0,0 +1,27 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Gauges exported in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    gauges: Mutex<BTreeMap<&'static str, (&'static str, f64)>>,
+}
+
+impl Metrics {
+    /// Sets gauge `name`, described by `help`, to `value`.
+    pub fn set(&self, name: &'static str, help: &'static str, value: f64) {
+        self.gauges.lock().unwrap().insert(name, (help, value));
+    }
+
+    /// Renders every gauge for a `/metrics` scrape.
+    pub fn render(&self) -> String {
+        let mut body = String::new();
+        for (name, (help, value)) in self.gauges.lock().unwrap().iter() {
+            let _ = writeln!(body, "# HELP {name} {help}");
+            let _ = writeln!(body, "# TYPE {name} gauge");
+            let _ = writeln!(body, "{name} {value}");
+        }
+        body
+    }
+}
@@ -0,0 +1,410 @@
+use crate::ledger::now;
+use reqwest::Client;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Webhook destinations and delivery limits.
+pub struct NotifySettings {
+    /// Receive the raw incident as JSON.
+    pub webhook_urls: Vec<String>,
+    /// Receive Slack incoming webhook payloads.
+    pub slack_webhook_urls: Vec<String>,
+    /// Receive Discord webhook payloads.
+    pub discord_webhook_urls: Vec<String>,
+    /// Identical incidents within this window are sent once.
+    pub dedup_window: Duration,
+    pub max_per_minute: usize,
+}
+
+/// An incident worth telling operators about.
+pub struct Incident {
+    /// Incident type, e.g. `failed_migration`.
+    pub kind: &'static str,
+    /// What the incident is about, e.g. a transfer id; incidents are deduplicated by kind and subject.
+    pub subject: String,
+    pub title: String,
+    pub detail: String,
+}
+
+struct Limiter {
+    last_sent: HashMap<(&'static str, String), Instant>,
+    sent: VecDeque<Instant>,
+    suppressed: usize,
+}
+
+/// Queues incidents for webhook delivery, dropping duplicates and bursts.
+pub struct Notifier<'a> {
+    settings: &'a NotifySettings,
+    sender: mpsc::UnboundedSender<(Incident, usize)>,
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<(Incident, usize)>>>,
+    limiter: Mutex<Limiter>,
+}
+
+impl<'a> Notifier<'a> {
+    pub fn new(settings: &'a NotifySettings) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            settings,
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            limiter: Mutex::new(Limiter {
+                last_sent: HashMap::new(),
+                sent: VecDeque::new(),
+                suppressed: 0,
+            }),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !(self.settings.webhook_urls.is_empty()
+            && self.settings.slack_webhook_urls.is_empty()
+            && self.settings.discord_webhook_urls.is_empty())
+    }
+
+    /// Queues `incident` unless it duplicates a recent one or the rate limit is reached.
+    pub fn notify(&self, incident: Incident) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut limiter = self.limiter.lock().unwrap();
+        let now = Instant::now();
+        let key = (incident.kind, incident.subject.clone());
+
+        if limiter
+            .last_sent
+            .get(&key)
+            .is_some_and(|sent| now.duration_since(*sent) < self.settings.dedup_window)
+        {
+            return;
+        }
+
+        while limiter
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= Duration::from_secs(60))
+        {
+            limiter.sent.pop_front();
+        }
+        if limiter.sent.len() >= self.settings.max_per_minute {
+            limiter.suppressed += 1;
+            return;
+        }
+
+        let dedup_window = self.settings.dedup_window;
+        limiter
+            .last_sent
+            .retain(|_, sent| now.duration_since(*sent) < dedup_window);
+        limiter.last_sent.insert(key, now);
+        limiter.sent.push_back(now);
+        let suppressed = std::mem::take(&mut limiter.suppressed);
+
+        let _ = self.sender.send((incident, suppressed));
+    }
+
+    /// Delivers queued incidents to every configured webhook.
+    pub async fn run(&self) {
+        let mut receiver = match self.receiver.lock().unwrap().take() {
+            Some(receiver) => receiver,
+            None => return,
+        };
+        let client = Client::new();
+
+        while let Some((incident, suppressed)) = receiver.recv().await {
+            self.deliver(&client, &incident, suppressed).await;
+        }
+    }
+
+    /// Posts `incident` to every configured webhook, returning how many deliveries failed.
+    async fn deliver(&self, client: &Client, incident: &Incident, suppressed: usize) -> usize {
+        let mut detail = incident.detail.clone();
+        if suppressed > 0 {
+            detail.push_str(&format!(
+                "\n({suppressed} earlier notifications dropped by rate limit)"
+            ));
+        }
+
+        let raw = json!({
+            "kind": incident.kind,
+            "subject": incident.subject,
+            "title": incident.title,
+            "detail": detail,
+            "suppressed": suppressed,
+            "at": now(),
+        });
+        let slack = json!({ "text": format!("*{}*\n{detail}", incident.title) });
+        let discord = json!({ "content": format!("**{}**\n{detail}", incident.title) });
+
+        let deliveries = self
+            .settings
+            .webhook_urls
+            .iter()
+            .map(|url| (url, &raw))
+            .chain(
+                self.settings
+                    .slack_webhook_urls
+                    .iter()
+                    .map(|url| (url, &slack)),
+            )
+            .chain(
+                self.settings
+                    .discord_webhook_urls
+                    .iter()
+                    .map(|url| (url, &discord)),
+            );
+
+        let mut failed = 0;
+        for (url, payload) in deliveries {
+            let result = client
+                .post(url)
+                .timeout(Duration::from_secs(10))
+                .json(payload)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+
+            if let Err(err) = result {
+                failed += 1;
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Failed to Send Notification
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Incident:    {}
+                            Status:      Failed❌
+                            Error:       {err}
+                ",
+                    incident.kind
+                );
+            }
+        }
+        failed
+    }
+}
+
+/// Runs `notify test`, posting a sample incident to every configured webhook.
+pub async fn run_notify(args: &[String], settings: &NotifySettings) {
+    if args.first().map(String::as_str) != Some("test") {
+        panic!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Usage: voip-relayer-rs notify test
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        "
+        );
+    }
+
+    let notifier = Notifier::new(settings);
+    if !notifier.is_enabled() {
+        panic!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                No Notification Webhooks Set
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        "
+        );
+    }
+
+    let incident = Incident {
+        kind: "test",
+        subject: "test".to_string(),
+        title: "Relayer test notification".to_string(),
+        detail: "Webhook delivery is working".to_string(),
+    };
+    let failed = notifier.deliver(&Client::new(), &incident, 0).await;
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    println!(
+        "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Sent Test Notification
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Success✅
+        "
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use serde_json::Value;
+    use std::convert::Infallible;
+
+    /// Starts a local webhook stand-in, returning its URL and the path and body of every post it receives.
+    fn listen() -> (String, mpsc::UnboundedReceiver<(String, Value)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let make_service = make_service_fn(move |_| {
+            let sender = sender.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let sender = sender.clone();
+                    async move {
+                        let path = request.uri().path().to_string();
+                        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                        let _ = sender.send((path, serde_json::from_slice(&body).unwrap()));
+                        Ok::<_, Infallible>(Response::new(Body::empty()))
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        (url, receiver)
+    }
+
+    fn settings(url: &str, max_per_minute: usize) -> NotifySettings {
+        NotifySettings {
+            webhook_urls: vec![format!("{url}/raw")],
+            slack_webhook_urls: vec![format!("{url}/slack")],
+            discord_webhook_urls: vec![format!("{url}/discord")],
+            dedup_window: Duration::from_secs(60),
+            max_per_minute,
+        }
+    }
+
+    fn incident(kind: &'static str, subject: &str) -> Incident {
+        Incident {
+            kind,
+            subject: subject.to_string(),
+            title: "Migration failed".to_string(),
+            detail: format!("{kind} for {subject}"),
+        }
+    }
+
+    /// Incidents queued so far, with how many were suppressed before each.
+    fn queued(notifier: &Notifier) -> Vec<(String, usize)> {
+        let mut receiver = notifier.receiver.lock().unwrap();
+        let receiver = receiver.as_mut().unwrap();
+        let mut queued = Vec::new();
+        while let Ok((incident, suppressed)) = receiver.try_recv() {
+            queued.push((
+                format!("{}:{}", incident.kind, incident.subject),
+                suppressed,
+            ));
+        }
+        queued
+    }
+
+    #[test]
+    fn drops_duplicates_by_kind_and_subject() {
+        let settings = settings("http://127.0.0.1:9", 10);
+        let notifier = Notifier::new(&settings);
+
+        notifier.notify(incident("failed_migration", "a"));
+        notifier.notify(incident("failed_migration", "a"));
+        notifier.notify(incident("failed_migration", "b"));
+        notifier.notify(incident("failed_burn", "a"));
+
+        assert_eq!(
+            queued(&notifier),
+            vec![
+                ("failed_migration:a".to_string(), 0),
+                ("failed_migration:b".to_string(), 0),
+                ("failed_burn:a".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn rate_limits_per_minute_and_counts_what_was_dropped() {
+        let settings = settings("http://127.0.0.1:9", 2);
+        let notifier = Notifier::new(&settings);
+
+        for subject in ["a", "b", "c", "d"] {
+            notifier.notify(incident("failed_migration", subject));
+        }
+        assert_eq!(queued(&notifier).len(), 2);
+
+        // once the minute has passed, the next incident reports the dropped ones
+        notifier.limiter.lock().unwrap().sent.clear();
+        notifier.notify(incident("failed_migration", "e"));
+        assert_eq!(
+            queued(&notifier),
+            vec![("failed_migration:e".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn nothing_is_queued_without_webhooks() {
+        let settings = NotifySettings {
+            webhook_urls: Vec::new(),
+            slack_webhook_urls: Vec::new(),
+            discord_webhook_urls: Vec::new(),
+            dedup_window: Duration::from_secs(60),
+            max_per_minute: 10,
+        };
+        let notifier = Notifier::new(&settings);
+        notifier.notify(incident("failed_migration", "a"));
+        assert!(queued(&notifier).is_empty());
+    }
+
+    #[tokio::test]
+    async fn delivers_raw_slack_and_discord_payloads() {
+        let (url, mut posts) = listen();
+        let settings = settings(&url, 10);
+        let notifier = Notifier::new(&settings);
+        notifier.notify(incident("failed_migration", "a"));
+
+        let mut received = tokio::select! {
+            _ = notifier.run() => unreachable!("the queue stays open"),
+            received = async {
+                let mut received = Vec::new();
+                while received.len() < 3 {
+                    received.push(posts.recv().await.unwrap());
+                }
+                received
+            } => received,
+        };
+        received.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let (path, discord) = &received[0];
+        assert_eq!(path, "/discord");
+        assert_eq!(
+            discord["content"],
+            "**Migration failed**\nfailed_migration for a"
+        );
+
+        let (path, raw) = &received[1];
+        assert_eq!(path, "/raw");
+        assert_eq!(raw["kind"], "failed_migration");
+        assert_eq!(raw["subject"], "a");
+        assert_eq!(raw["title"], "Migration failed");
+        assert_eq!(raw["detail"], "failed_migration for a");
+        assert_eq!(raw["suppressed"], 0);
+        assert!(raw["at"].as_u64().is_some());
+
+        let (path, slack) = &received[2];
+        assert_eq!(path, "/slack");
+        assert_eq!(slack["text"], "*Migration failed*\nfailed_migration for a");
+    }
+
+    #[tokio::test]
+    async fn notes_suppressed_incidents_and_counts_failed_deliveries() {
+        let (url, mut posts) = listen();
+        let mut settings = settings(&url, 10);
+        // nothing listens on the discard port
+        settings.discord_webhook_urls = vec!["http://127.0.0.1:9/discord".to_string()];
+        let notifier = Notifier::new(&settings);
+
+        let failed = notifier
+            .deliver(&Client::new(), &incident("failed_burn", "a"), 3)
+            .await;
+        assert_eq!(failed, 1);
+
+        let (_, raw) = posts.recv().await.unwrap();
+        assert_eq!(raw["suppressed"], 3);
+        assert_eq!(
+            raw["detail"],
+            "failed_burn for a\n(3 earlier notifications dropped by rate limit)"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use web3::signing::keccak256;
+use web3::types::H160;
+
+/// The share of locks an instance relays, split by hash of the Ethereum user.
+#[derive(Debug, Clone, Copy)]
+pub struct Partition {
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Partition {
+    /// Partition of `user` among `count`.
+    pub fn of(user: H160, count: u64) -> u64 {
+        let hash = keccak256(user.as_bytes());
+        let mut prefix = [0u8; 8];
+        prefix.copy_from_slice(&hash[..8]);
+        u64::from_be_bytes(prefix) % count.max(1)
+    }
+
+    pub fn owns(&self, user: H160) -> bool {
+        Self::of(user, self.count) == self.index
+    }
+
+    /// `path` with the partition index before its extension, unchanged when there is a single partition.
+    pub fn path(&self, path: &str) -> String {
+        if self.count <= 1 {
+            return path.to_string();
+        }
+        match path.rsplit_once('.') {
+            Some((stem, extension)) if !extension.contains('/') && !stem.is_empty() => {
+                format!("{stem}.{}.{extension}", self.index)
+            }
+            _ => format!("{path}.{}", self.index),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_user_is_owned_by_exactly_one_partition() {
+        for byte in 0..=u8::MAX {
+            let user = H160::repeat_byte(byte);
+            let index = Partition::of(user, 3);
+            assert!(index < 3);
+            assert_eq!(Partition::of(user, 3), index);
+            let owners = (0..3)
+                .filter(|index| {
+                    Partition {
+                        index: *index,
+                        count: 3,
+                    }
+                    .owns(user)
+                })
+                .count();
+            assert_eq!(owners, 1);
+        }
+
+        // a single partition owns everything
+        assert_eq!(Partition::of(H160::repeat_byte(7), 1), 0);
+        assert_eq!(Partition::of(H160::repeat_byte(7), 0), 0);
+    }
+
+    #[test]
+    fn spreads_users_over_partitions() {
+        let mut counts = [0; 4];
+        for byte in 0..=u8::MAX {
+            counts[Partition::of(H160::repeat_byte(byte), 4) as usize] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 0));
+    }
+
+    #[test]
+    fn numbers_paths_only_when_partitioned() {
+        let single = Partition { index: 0, count: 1 };
+        assert_eq!(single.path("ledger.json"), "ledger.json");
+
+        let second = Partition { index: 1, count: 3 };
+        assert_eq!(second.path("ledger.json"), "ledger.1.json");
+        assert_eq!(second.path("/data/relayer.lease"), "/data/relayer.1.lease");
+        assert_eq!(second.path("/data.d/ledger"), "/data.d/ledger.1");
+        assert_eq!(second.path(".ledger"), ".ledger.1");
+    }
+}
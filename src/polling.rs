@@ -0,0 +1,324 @@
+use crate::endpoints::{Endpoints, FailoverHttp};
+use crate::ledger::Ledger;
+use crate::relay::Relayer;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+use web3::api::SubscriptionStream;
+use web3::futures::{stream, StreamExt};
+use web3::transports::WebSocket;
+use web3::types::{BlockHeader, BlockNumber, FilterBuilder, U64};
+
+/// Where bridge events are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSource {
+    /// `eth_subscribe` over the websocket endpoints.
+    Websocket,
+    /// `eth_getLogs` over the HTTP endpoints.
+    Polling,
+    /// The websocket, polling while it cannot be reached.
+    Auto,
+}
+
+impl FromStr for EventSource {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "websocket" => Ok(Self::Websocket),
+            "polling" => Ok(Self::Polling),
+            "auto" => Ok(Self::Auto),
+            _ => Err(format!("unknown event source {source}")),
+        }
+    }
+}
+
+/// How `eth_getLogs` polling walks the chain.
+pub struct PollSettings {
+    pub interval: Duration,
+    /// Most blocks requested in a single `eth_getLogs` call.
+    pub max_blocks: u64,
+    /// Where a ledger without a block cursor starts, the chain head when unset.
+    pub start_block: Option<u64>,
+}
+
+impl PollSettings {
+    /// First block to read: the ledger's cursor, which the websocket also advances, then the configured start.
+    pub fn first_block(&self, ledger: &Ledger) -> Option<u64> {
+        ledger.next_block().or(self.start_block)
+    }
+}
+
+/// Walks `from..=head` in ranges of at most `max_blocks`, moving the cursor past each range once `process` succeeds.
+async fn walk<F, Fut>(
+    ledger: &Ledger,
+    mut from: u64,
+    head: u64,
+    max_blocks: u64,
+    mut process: F,
+) -> web3::Result<()>
+where
+    F: FnMut(u64, u64) -> Fut,
+    Fut: Future<Output = web3::Result<()>>,
+{
+    while from <= head {
+        let to = head.min(from.saturating_add(max_blocks.max(1) - 1));
+        process(from, to).await?;
+        ledger.advance_cursor(to + 1);
+        from = to + 1;
+    }
+    Ok(())
+}
+
+impl Relayer<'_> {
+    /// Polls `eth_getLogs` for `filter` from the ledger's block cursor, processing each batch before moving on, until `stop` resolves.
+    pub async fn poll_logs(
+        &self,
+        eth: &web3::api::Eth<FailoverHttp>,
+        filter: &FilterBuilder,
+        settings: &PollSettings,
+        stop: impl Future,
+    ) {
+        tokio::pin!(stop);
+        loop {
+            if let Err(err) = self.poll_once(eth, filter, settings).await {
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Failed to Poll Logs
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Failed❌
+                            Error:       {err}
+                "
+                );
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(settings.interval) => {}
+                _ = &mut stop => return,
+            }
+        }
+    }
+
+    /// Catches up on blocks missed while disconnected, from the cursor to the chain head.
+    pub async fn backfill(
+        &self,
+        eth: &web3::api::Eth<FailoverHttp>,
+        filter: &FilterBuilder,
+        settings: &PollSettings,
+    ) {
+        let from = match settings.first_block(self.ledger) {
+            Some(from) => from,
+            None => return,
+        };
+
+        match self.poll_once(eth, filter, settings).await {
+            Ok(()) => println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Backfilled Missed Blocks
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            From Block:  {from}
+                            Status:      Success✅
+                "
+            ),
+            Err(err) => println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Failed to Backfill Missed Blocks
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            From Block:  {from}
+                            Status:      Failed❌
+                            Error:       {err}
+                "
+            ),
+        }
+    }
+
+    /// Processes every block from the cursor up to the chain head, one range at a time.
+    async fn poll_once(
+        &self,
+        eth: &web3::api::Eth<FailoverHttp>,
+        filter: &FilterBuilder,
+        settings: &PollSettings,
+    ) -> web3::Result<()> {
+        let head = eth.block_number().await?.as_u64();
+        let from = settings.first_block(self.ledger).unwrap_or(head);
+
+        walk(
+            self.ledger,
+            from,
+            head,
+            settings.max_blocks,
+            |from, to| async move {
+                let range = filter
+                    .clone()
+                    .from_block(BlockNumber::Number(U64::from(from)))
+                    .to_block(BlockNumber::Number(U64::from(to)))
+                    .build();
+                let logs = eth.logs(range).await?;
+
+                stream::iter(logs)
+                    .for_each_concurrent(20, |log| self.process_log(log))
+                    .await;
+                Ok(())
+            },
+        )
+        .await
+    }
+}
+
+/// Returns once `heads` goes quiet for `stall_timeout` while the HTTP endpoints see the chain advance.
+pub async fn monitor_heads(
+    mut heads: SubscriptionStream<WebSocket, BlockHeader>,
+    eth: &web3::api::Eth<FailoverHttp>,
+    ledger: &Ledger,
+    wss_endpoints: &Endpoints,
+    index: usize,
+    stall_timeout: Duration,
+) {
+    let mut last_head: Option<u64> = None;
+    let reason = loop {
+        match tokio::time::timeout(stall_timeout, heads.next()).await {
+            Ok(Some(Ok(head))) => {
+                let number = match head.number {
+                    Some(number) => number.as_u64(),
+                    None => continue,
+                };
+
+                // delivered logs are recorded on arrival, and the previous block's have had a block's time to arrive
+                if let Some(last_head) = last_head {
+                    ledger.advance_cursor(last_head);
+                }
+                last_head = Some(number);
+            }
+            Ok(Some(Err(err))) => break err.to_string(),
+            Ok(None) => break "new heads subscription closed".to_string(),
+            Err(_) => {
+                // a quiet subscription is only stalled when the chain moved on
+                if let Ok(height) = eth.block_number().await {
+                    let height = height.as_u64();
+                    if last_head.is_none_or(|last_head| height > last_head) {
+                        break format!(
+                            "no new heads for {stall_timeout:?}, chain at block {height}"
+                        );
+                    }
+                }
+            }
+        }
+    };
+
+    wss_endpoints.record(index, false);
+    println!(
+        "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Websocket Stalled: {}
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Reason:      {reason}
+                            Status:      Processing ♻️
+                ",
+        wss_endpoints.label(index)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn ledger() -> Ledger {
+        Ledger::open("/nonexistent/ledger.json", true).unwrap()
+    }
+
+    fn settings(start_block: Option<u64>) -> PollSettings {
+        PollSettings {
+            interval: Duration::from_secs(12),
+            max_blocks: 10,
+            start_block,
+        }
+    }
+
+    /// Ranges `walk` hands to `process`, failing on the range starting at `fail_at`.
+    async fn walked(
+        ledger: &Ledger,
+        from: u64,
+        head: u64,
+        max_blocks: u64,
+        fail_at: Option<u64>,
+    ) -> (Vec<(u64, u64)>, bool) {
+        let ranges = RefCell::new(Vec::new());
+        let result = walk(ledger, from, head, max_blocks, |from, to| {
+            ranges.borrow_mut().push((from, to));
+            async move {
+                if fail_at == Some(from) {
+                    Err(web3::Error::Unreachable)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+        (ranges.into_inner(), result.is_ok())
+    }
+
+    #[test]
+    fn first_block_prefers_the_cursor_over_the_configured_start() {
+        let ledger = ledger();
+        assert_eq!(settings(None).first_block(&ledger), None);
+        assert_eq!(settings(Some(500)).first_block(&ledger), Some(500));
+
+        // the websocket moved the cursor before polling took over
+        ledger.advance_cursor(800);
+        assert_eq!(settings(Some(500)).first_block(&ledger), Some(800));
+        assert_eq!(settings(None).first_block(&ledger), Some(800));
+    }
+
+    #[tokio::test]
+    async fn walks_up_to_the_head_in_ranges_of_max_blocks() {
+        let ledger = ledger();
+        let (ranges, ok) = walked(&ledger, 100, 124, 10, None).await;
+
+        assert!(ok);
+        assert_eq!(ranges, [(100, 109), (110, 119), (120, 124)]);
+        assert_eq!(ledger.next_block(), Some(125));
+    }
+
+    #[tokio::test]
+    async fn a_single_block_range_and_a_zero_max_still_advance() {
+        let ledger = ledger();
+        let (ranges, _) = walked(&ledger, 7, 7, 1000, None).await;
+        assert_eq!(ranges, [(7, 7)]);
+        assert_eq!(ledger.next_block(), Some(8));
+
+        let (ranges, _) = walked(&ledger, 8, 10, 0, None).await;
+        assert_eq!(ranges, [(8, 8), (9, 9), (10, 10)]);
+        assert_eq!(ledger.next_block(), Some(11));
+    }
+
+    #[tokio::test]
+    async fn nothing_is_walked_once_past_the_head() {
+        let ledger = ledger();
+        ledger.advance_cursor(51);
+        let (ranges, ok) = walked(&ledger, 51, 50, 10, None).await;
+
+        assert!(ok);
+        assert!(ranges.is_empty());
+        assert_eq!(ledger.next_block(), Some(51));
+    }
+
+    #[tokio::test]
+    async fn a_failed_range_leaves_the_cursor_at_its_start() {
+        let ledger = ledger();
+        let (ranges, ok) = walked(&ledger, 100, 130, 10, Some(110)).await;
+
+        assert!(!ok);
+        assert_eq!(ranges, [(100, 109), (110, 119)]);
+        assert_eq!(ledger.next_block(), Some(110));
+
+        // the next poll retries from there
+        let from = settings(None).first_block(&ledger).unwrap();
+        let (ranges, ok) = walked(&ledger, from, 130, 10, None).await;
+        assert!(ok);
+        assert_eq!(ranges, [(110, 119), (120, 129), (130, 130)]);
+        assert_eq!(ledger.next_block(), Some(131));
+    }
+}
@@ -0,0 +1,681 @@
+use crate::amount::convert_amount;
+use crate::bridge::{decode_lock, TOKENS_BURNED_TOPIC, TOKENS_LOCKED_TOPIC};
+use crate::endpoints::{solana_client, Endpoints, FailoverHttp};
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use anchor_client::solana_client::rpc_config::RpcTransactionConfig;
+use anchor_client::solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use anchor_client::solana_sdk::{pubkey::Pubkey, signature::Signature};
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::Mint;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use web3::contract::{Contract, Options};
+use web3::ethabi::{decode, ParamType};
+use web3::types::{Address, BlockId, BlockNumber, FilterBuilder, Log, H160, H256, U256};
+
+// keep each eth_getLogs request within common provider range limits
+const LOG_BLOCK_CHUNK: u64 = 5_000;
+
+// most signatures `getSignaturesForAddress` returns per page
+const SIGNATURE_PAGE: usize = 1_000;
+
+/// A `TokensLocked` event found in the scanned range.
+pub struct Lock {
+    pub eth_tx_hash: Option<H256>,
+    pub eth_user: H160,
+    pub solana_address: String,
+    pub eth_amount: U256,
+}
+
+/// What Ethereum shows for one lock of a destination.
+pub struct LockCheck {
+    /// Locked amount scaled to SPL decimals.
+    pub expected: u64,
+    /// A `TokensBurned` event for the lock's user and amount was found.
+    pub burned: bool,
+    /// The bridge holds nothing for the lock's user and destination any more.
+    pub released: bool,
+}
+
+/// How a single lock was settled across both chains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Settlement {
+    /// Migrated in full and burned.
+    Completed,
+    /// Released without a migration or burn.
+    Refunded,
+    /// Not migrated, while still locked or burned anyway.
+    Unmigrated,
+    /// Migrated in full without a matching burn.
+    Unburned,
+    /// Migrated less than was locked.
+    AmountMismatched(u64),
+    /// Migrated more than was locked.
+    OverMigrated(u64),
+}
+
+/// Settles each of a destination's locks against the amounts its migrations delivered, returning the migrations no lock accounts for.
+pub fn settle(locks: &[LockCheck], received: &[u64]) -> (Vec<Settlement>, Vec<u64>) {
+    let mut unmatched = received.to_vec();
+
+    // exact amounts first, so a migration is never credited to a lock it does not belong to
+    let mut migrated: Vec<Option<u64>> = locks
+        .iter()
+        .map(|lock| {
+            let index = unmatched
+                .iter()
+                .position(|amount| *amount == lock.expected)?;
+            Some(unmatched.remove(index))
+        })
+        .collect();
+
+    // a lock that was not refunded takes the oldest migration left, whatever its amount
+    for (lock, migrated) in locks.iter().zip(migrated.iter_mut()) {
+        if migrated.is_none() && (lock.burned || !lock.released) && !unmatched.is_empty() {
+            *migrated = Some(unmatched.remove(0));
+        }
+    }
+
+    let settlements = locks
+        .iter()
+        .zip(migrated)
+        .map(|(lock, migrated)| match migrated {
+            None if lock.released && !lock.burned => Settlement::Refunded,
+            None => Settlement::Unmigrated,
+            Some(amount) if amount < lock.expected => Settlement::AmountMismatched(amount),
+            Some(amount) if amount > lock.expected => Settlement::OverMigrated(amount),
+            Some(_) if !lock.burned => Settlement::Unburned,
+            Some(_) => Settlement::Completed,
+        })
+        .collect();
+
+    (settlements, unmatched)
+}
+
+/// Discrepancies between Ethereum locks and Solana migrations.
+#[derive(Default)]
+pub struct Report {
+    pub locks: usize,
+    pub refunded: usize,
+    pub unmigrated: Vec<String>,
+    pub unburned: Vec<String>,
+    pub over_migrated: Vec<String>,
+    pub amount_mismatched: Vec<String>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.unmigrated.is_empty()
+            && self.unburned.is_empty()
+            && self.over_migrated.is_empty()
+            && self.amount_mismatched.is_empty()
+    }
+}
+
+/// Compares every `TokensLocked` event in `from_block..=to_block` against Solana migrations and `TokensBurned` events.
+pub async fn reconcile(
+    eth: &web3::api::Eth<FailoverHttp>,
+    contract: &Contract<FailoverHttp>,
+    connection: &RpcClient,
+    sol_voip_token_mint: &Pubkey,
+    voip_migration_program_id: &Pubkey,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Report, Box<dyn std::error::Error>> {
+    let bridge = contract.address();
+
+    // scale lock amounts the same way the relayer does
+    let eth_voip_token_address: Address = contract
+        .query("voipToken", (), None, Options::default(), None)
+        .await?;
+    let eth_voip_token = Contract::from_json(
+        eth.clone(),
+        eth_voip_token_address,
+        include_bytes!("../artifacts/eth/token/token.json"),
+    )?;
+    let eth_decimals: u8 = eth_voip_token
+        .query("decimals", (), None, Options::default(), None)
+        .await?;
+    let mint_account = connection.get_account(sol_voip_token_mint).await?;
+    let sol_decimals = StateWithExtensions::<Mint>::unpack(&mint_account.data)?
+        .base
+        .decimals;
+
+    // locks before the range were migrated before its first block, so only later migrations count
+    let since = eth
+        .block(BlockId::Number(BlockNumber::Number(from_block.into())))
+        .await?
+        .ok_or("from block not found")?
+        .timestamp
+        .as_u64();
+
+    // burns land after their lock, so scan them up to the chain head
+    let latest_block = eth.block_number().await?.as_u64();
+    let lock_logs = get_logs(eth, bridge, TOKENS_LOCKED_TOPIC, from_block, to_block).await?;
+    let burn_logs = get_logs(eth, bridge, TOKENS_BURNED_TOPIC, from_block, latest_block).await?;
+
+    let mut burns: Vec<(H160, U256)> = burn_logs
+        .iter()
+        .filter_map(|log| {
+            let user = H160::from(*log.topics.get(1)?);
+            let data = decode(&[ParamType::Uint(256), ParamType::Uint(256)], &log.data.0).ok()?;
+            Some((user, data.first()?.clone().into_uint()?))
+        })
+        .collect();
+
+    // group locks by destination, as migration PDAs are per destination
+    let mut destinations: BTreeMap<String, Vec<Lock>> = BTreeMap::new();
+    for log in &lock_logs {
+        if let Some((eth_amount, eth_user, solana_address)) = decode_lock(log) {
+            destinations
+                .entry(solana_address.clone())
+                .or_default()
+                .push(Lock {
+                    eth_tx_hash: log.transaction_hash,
+                    eth_user,
+                    solana_address,
+                    eth_amount,
+                });
+        }
+    }
+
+    let mut report = Report::default();
+    let mut locked_balances: BTreeMap<(H160, String), bool> = BTreeMap::new();
+    for (solana_address, locks) in &destinations {
+        report.locks += locks.len();
+
+        let received = match Pubkey::from_str(solana_address) {
+            Ok(destination) => {
+                read_migrations(
+                    connection,
+                    voip_migration_program_id,
+                    sol_voip_token_mint,
+                    &destination,
+                    since,
+                )
+                .await?
+            }
+            Err(_) => Vec::new(),
+        };
+
+        let mut checks = Vec::new();
+        for lock in locks {
+            let key = (lock.eth_user, lock.solana_address.clone());
+            let released = match locked_balances.get(&key) {
+                Some(released) => *released,
+                None => {
+                    let locked_balance: U256 = contract
+                        .query(
+                            "lockedBalances",
+                            key.clone(),
+                            None,
+                            Options::default(),
+                            None,
+                        )
+                        .await?;
+                    *locked_balances
+                        .entry(key)
+                        .or_insert(locked_balance.is_zero())
+                }
+            };
+
+            // each burn event can only account for one lock
+            let burned = match burns
+                .iter()
+                .position(|burn| *burn == (lock.eth_user, lock.eth_amount))
+            {
+                Some(index) => {
+                    burns.swap_remove(index);
+                    true
+                }
+                None => false,
+            };
+
+            checks.push(LockCheck {
+                expected: convert_amount(lock.eth_amount, eth_decimals, sol_decimals)
+                    .unwrap_or_default(),
+                burned,
+                released,
+            });
+        }
+
+        let (settlements, unmatched) = settle(&checks, &received);
+        for ((lock, check), settlement) in locks.iter().zip(&checks).zip(settlements) {
+            let entry = |migrated: u64| {
+                format!(
+                    "{:?} from {:?} to {solana_address} (expected: {}, migrated: {migrated})",
+                    lock.eth_tx_hash.unwrap_or_default(),
+                    lock.eth_user,
+                    check.expected
+                )
+            };
+            match settlement {
+                Settlement::Completed => {}
+                Settlement::Refunded => report.refunded += 1,
+                Settlement::Unmigrated => report.unmigrated.push(entry(0)),
+                Settlement::Unburned => report.unburned.push(entry(check.expected)),
+                Settlement::AmountMismatched(migrated) => {
+                    report.amount_mismatched.push(entry(migrated))
+                }
+                Settlement::OverMigrated(migrated) => report.over_migrated.push(entry(migrated)),
+            }
+        }
+        for migrated in unmatched {
+            report.over_migrated.push(format!(
+                "{solana_address} (migrated: {migrated}, matching no lock)"
+            ));
+        }
+    }
+
+    Ok(report)
+}
+
+/// What `destination` received in each successful transaction on its migration PDA at or after unix time `since`, oldest first.
+async fn read_migrations(
+    connection: &RpcClient,
+    voip_migration_program_id: &Pubkey,
+    sol_voip_token_mint: &Pubkey,
+    destination: &Pubkey,
+    since: u64,
+) -> Result<Vec<u64>, Box<dyn std::error::Error>> {
+    let (migration_pda, _) = Pubkey::find_program_address(
+        &[&b"migration"[..], destination.as_ref()],
+        voip_migration_program_id,
+    );
+
+    let mut received = Vec::new();
+    if connection
+        .get_account_with_commitment(&migration_pda, connection.commitment())
+        .await?
+        .value
+        .is_none()
+    {
+        return Ok(received);
+    }
+
+    // newest first, so stop at the first migration older than the range
+    for status in signatures_for_address(connection, &migration_pda, None, Some(since)).await? {
+        if status
+            .block_time
+            .is_some_and(|block_time| (block_time as u64) < since)
+        {
+            break;
+        }
+        if status.err.is_some() {
+            continue;
+        }
+
+        // destination ATA balance change for the VOIP mint
+        let signature = Signature::from_str(&status.signature)?;
+        match token_balance_change(connection, &signature, sol_voip_token_mint, destination).await?
+        {
+            Some(change) => received.push(u64::try_from(change.max(0))?),
+            None => continue,
+        }
+    }
+
+    received.reverse();
+    Ok(received)
+}
+
+/// How much `owner`'s `mint` balance changed in the successful transaction `signature`, `None` when it failed.
+pub async fn token_balance_change(
+    connection: &RpcClient,
+    signature: &Signature,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Option<i128>, Box<dyn std::error::Error>> {
+    let transaction = connection
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: None,
+                commitment: Some(connection.commitment()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?;
+    let meta = match transaction.transaction.meta {
+        Some(meta) if meta.err.is_none() => meta,
+        _ => return Ok(None),
+    };
+
+    let mint = mint.to_string();
+    let owner = owner.to_string();
+    let post: Option<Vec<_>> = meta.post_token_balances.into();
+    let pre: Option<Vec<_>> = meta.pre_token_balances.into();
+    let mut change: i128 = 0;
+    for (sign, balances) in [(1, post), (-1, pre)] {
+        for balance in balances.unwrap_or_default() {
+            if balance.mint == mint && Option::from(balance.owner) == Some(owner.clone()) {
+                change += sign * balance.ui_token_amount.amount.parse::<i128>()?;
+            }
+        }
+    }
+    Ok(Some(change))
+}
+
+/// Every signature touching `address` after `until`, newest first, paging back to the account's first transaction when `until` is `None`.
+/// With `since`, paging stops once a page reaches transactions older than that unix time.
+pub async fn signatures_for_address(
+    connection: &RpcClient,
+    address: &Pubkey,
+    until: Option<Signature>,
+    since: Option<u64>,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, Box<dyn std::error::Error>> {
+    let mut signatures = Vec::new();
+    let mut before = None;
+    loop {
+        let page = connection
+            .get_signatures_for_address_with_config(
+                address,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(SIGNATURE_PAGE),
+                    commitment: Some(connection.commitment()),
+                },
+            )
+            .await?;
+        let last = match page.last() {
+            Some(last) => Signature::from_str(&last.signature)?,
+            None => break,
+        };
+        let full = page.len() >= SIGNATURE_PAGE;
+        let older = page
+            .last()
+            .and_then(|last| last.block_time)
+            .is_some_and(|block_time| since.is_some_and(|since| (block_time as u64) < since));
+        signatures.extend(page);
+        if !full || older {
+            break;
+        }
+        before = Some(last);
+    }
+
+    Ok(signatures)
+}
+
+async fn get_logs(
+    eth: &web3::api::Eth<FailoverHttp>,
+    bridge: Address,
+    topic: [u8; 32],
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<Log>, web3::Error> {
+    let mut logs = Vec::new();
+    let mut start = from_block;
+    while start <= to_block {
+        let end = to_block.min(start + LOG_BLOCK_CHUNK - 1);
+        let filter = FilterBuilder::default()
+            .address(vec![bridge])
+            .topics(Some(vec![topic.into()]), None, None, None)
+            .from_block(BlockNumber::Number(start.into()))
+            .to_block(BlockNumber::Number(end.into()))
+            .build();
+        logs.extend(eth.logs(filter).await?);
+        start = end + 1;
+    }
+
+    Ok(logs)
+}
+
+/// Prints `report` in the relayer's log format.
+pub fn print_report(report: &Report, from_block: u64, to_block: u64) {
+    for (title, entries) in [
+        ("Unmigrated Locks", &report.unmigrated),
+        ("Unburned Migrations", &report.unburned),
+        ("Over-migrated Destinations", &report.over_migrated),
+        ("Amount Mismatches", &report.amount_mismatched),
+    ] {
+        for entry in entries {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Reconciliation: {title}
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Entry:       {entry}
+                            Status:      Failed❌
+                "
+            );
+        }
+    }
+
+    let status = if report.is_clean() {
+        "Success✅"
+    } else {
+        "Failed❌"
+    };
+    println!(
+        "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Reconciliation Report
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Blocks:            {from_block} - {to_block}
+                        Locks:             {}
+                        Refunded:          {}
+                        Unmigrated:        {}
+                        Unburned:          {}
+                        Over-migrated:     {}
+                        Amount Mismatched: {}
+                        Status:            {status}
+            ",
+        report.locks,
+        report.refunded,
+        report.unmigrated.len(),
+        report.unburned.len(),
+        report.over_migrated.len(),
+        report.amount_mismatched.len()
+    );
+}
+
+/// Runs `reconcile <from_block> [to_block]`, exiting non-zero when discrepancies are found.
+pub async fn run_reconcile(
+    args: &[String],
+    eth_http_endpoints: Arc<Endpoints>,
+    eth_voip_bridge_address: &str,
+    sol_endpoints: Arc<Endpoints>,
+    sol_voip_mint_address: &str,
+    sol_voip_migration_address: &str,
+) {
+    let usage = "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Usage: voip-relayer-rs reconcile <from_block> [to_block]
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        ";
+    let from_block: u64 = args
+        .first()
+        .and_then(|block| block.parse().ok())
+        .expect(usage);
+
+    let http_web3 = match FailoverHttp::new(eth_http_endpoints) {
+        Ok(http) => web3::Web3::new(http),
+        Err(err) => panic!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Failed to setup transport layer, Use a dedicated Ethereum HTTP URL
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                        Status:      Failed❌
+                                        Error:       {err}
+            "
+        ),
+    };
+    let eth = http_web3.eth();
+
+    let to_block: u64 = match args.get(1) {
+        Some(block) => block.parse().expect(usage),
+        None => match eth.block_number().await {
+            Ok(block) => block.as_u64(),
+            Err(err) => panic!(
+                "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Failed to Get Latest Eth Block
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Status:      Failed❌
+                        Error:       {err}
+            "
+            ),
+        },
+    };
+
+    let contract = eth_voip_bridge_address
+        .parse::<Address>()
+        .map_err(|err| err.to_string())
+        .and_then(|address| {
+            Contract::from_json(
+                eth.clone(),
+                address,
+                include_bytes!("../artifacts/eth/bridge/bridge.json"),
+            )
+            .map_err(|err| err.to_string())
+        });
+    let contract = match contract {
+        Ok(contract) => contract,
+        Err(err) => panic!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Failed to Parse Eth Bridge Contract Address
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                        Status:      Failed❌
+                                        Error:       {err}
+            "
+        ),
+    };
+
+    let sol_voip_token_mint = Pubkey::from_str(sol_voip_mint_address).expect(
+        "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Failed to parse solana VOIP mint
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                Status:      Failed❌
+        ",
+    );
+    let voip_migration_program_id = Pubkey::from_str(sol_voip_migration_address).expect(
+        "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Failed to get migration program ID
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        ",
+    );
+    let connection = solana_client(sol_endpoints);
+
+    match reconcile(
+        &eth,
+        &contract,
+        &connection,
+        &sol_voip_token_mint,
+        &voip_migration_program_id,
+        from_block,
+        to_block,
+    )
+    .await
+    {
+        Ok(report) => {
+            print_report(&report, from_block, to_block);
+            if !report.is_clean() {
+                std::process::exit(1);
+            }
+        }
+        Err(err) => {
+            println!(
+                "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Failed to Reconcile
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Status:      Failed❌
+                        Error:       {err}
+            "
+            );
+            std::process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(expected: u64, burned: bool, released: bool) -> LockCheck {
+        LockCheck {
+            expected,
+            burned,
+            released,
+        }
+    }
+
+    #[test]
+    fn settles_migrated_and_burned_locks() {
+        let (settlements, unmatched) =
+            settle(&[lock(100, true, true), lock(50, true, false)], &[50, 100]);
+        assert_eq!(
+            settlements,
+            vec![Settlement::Completed, Settlement::Completed]
+        );
+        assert!(unmatched.is_empty());
+    }
+
+    #[test]
+    fn a_refunded_lock_does_not_hide_a_burned_sibling() {
+        // same user and destination, so the bridge shows both released
+        let (settlements, _) = settle(&[lock(100, false, true), lock(40, true, true)], &[]);
+        assert_eq!(
+            settlements,
+            vec![Settlement::Refunded, Settlement::Unmigrated]
+        );
+    }
+
+    #[test]
+    fn a_lock_still_held_without_a_migration_is_unmigrated() {
+        let (settlements, _) = settle(&[lock(100, false, false)], &[]);
+        assert_eq!(settlements, vec![Settlement::Unmigrated]);
+    }
+
+    #[test]
+    fn a_migrated_lock_without_a_burn_is_unburned() {
+        let (settlements, _) = settle(
+            &[lock(100, true, false), lock(70, false, false)],
+            &[70, 100],
+        );
+        assert_eq!(
+            settlements,
+            vec![Settlement::Completed, Settlement::Unburned]
+        );
+    }
+
+    #[test]
+    fn pairs_differing_amounts_after_exact_matches() {
+        let (settlements, unmatched) = settle(
+            &[
+                lock(100, true, true),
+                lock(60, true, true),
+                lock(30, true, true),
+            ],
+            &[100, 55, 35],
+        );
+        assert_eq!(
+            settlements,
+            vec![
+                Settlement::Completed,
+                Settlement::AmountMismatched(55),
+                Settlement::OverMigrated(35),
+            ]
+        );
+        assert!(unmatched.is_empty());
+    }
+
+    #[test]
+    fn reports_migrations_no_lock_accounts_for() {
+        let (settlements, unmatched) = settle(&[lock(100, true, true)], &[100, 100]);
+        assert_eq!(settlements, vec![Settlement::Completed]);
+        assert_eq!(unmatched, vec![100]);
+
+        // a refunded lock never takes a stray migration
+        let (settlements, unmatched) = settle(&[lock(100, false, true)], &[80]);
+        assert_eq!(settlements, vec![Settlement::Refunded]);
+        assert_eq!(unmatched, vec![80]);
+    }
+}
@@ -0,0 +1,1221 @@
+use crate::amount::convert_amount;
+use crate::balance::BalanceGuard;
+use crate::breaker::Breaker;
+use crate::bridge::{
+    decode_lock, report_ownership_transferred, verify_burn, OWNERSHIP_TRANSFERRED_TOPIC,
+};
+use crate::config::Settings;
+use crate::dry_run::refund_simulated;
+use crate::endpoints::FailoverHttp;
+use crate::gate::Gate;
+use crate::ledger::{now, AuditEntry, Decision, Ledger, Transfer, TransferStatus};
+use crate::notify::{Incident, Notifier};
+use crate::partition::Partition;
+use crate::resume::InFlight;
+use crate::roles::recheck_roles;
+use crate::screening::{Screening, ScreeningAction};
+use crate::shutdown::Shutdown;
+use crate::simulation::{
+    compute_budget_instructions, program_error, sign, simulate, ComputeBudget, Disposition,
+    ProgramError,
+};
+use crate::validation::DestinationValidator;
+use anchor_client::anchor_lang::prelude::AccountMeta;
+use anchor_client::solana_client::client_error::ClientError;
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+};
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use spl_token_2022::state::Mint;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use web3::contract::{Contract, Options};
+use web3::signing;
+use web3::types::{Address, Log, H160, H256, U256, U64};
+
+// how often a lock short of quorum is looked up again before it is held
+const QUORUM_ATTEMPTS: usize = 3;
+const QUORUM_RETRY: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Why a migrate transaction was not sent or did not land.
+#[derive(Debug)]
+pub enum MigrateError {
+    /// Simulation hit an error raised by the migration program, so resending cannot succeed.
+    Program(ProgramError),
+    /// Simulation failed for another reason, e.g. an underfunded admin ATA.
+    Simulation(String),
+    /// The transaction was sent but not confirmed, so it may still have landed.
+    Send(Signature, Box<ClientError>),
+}
+
+impl fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrateError::Program(err) => write!(f, "migration program error {err}"),
+            MigrateError::Simulation(err) => write!(f, "simulation failed: {err}"),
+            MigrateError::Send(_, err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// Connections, accounts and guards shared by every relayed event.
+pub struct Relayer<'a> {
+    pub contract: Contract<FailoverHttp>,
+    pub eth_transport: FailoverHttp,
+    pub partition: Partition,
+    /// Transfers this instance is working on right now.
+    pub in_flight: InFlight,
+    /// Bounds the locks processed at once, taken only after a lock is recorded.
+    pub lock_permits: Semaphore,
+    /// Set when stepping down, so transfers not yet migrated stop where they are.
+    pub shutdown: &'a Shutdown,
+    pub program: &'a anchor_client::Program<Arc<Keypair>>,
+    pub connection: &'a RpcClient,
+    pub ledger: &'a Ledger,
+    pub gate: &'a Gate,
+    pub balance_guard: &'a BalanceGuard<'a>,
+    pub destination_validator: &'a DestinationValidator,
+    pub screening: &'a Screening,
+    pub sol_breaker: &'a Breaker<'a>,
+    pub eth_breaker: &'a Breaker<'a>,
+    pub notifier: &'a Notifier<'a>,
+    pub settings: &'a Settings,
+    pub eth_admin_private_key: &'a signing::SecretKey,
+    pub eth_relayer: H160,
+    pub eth_voip_bridge_address: Address,
+    pub eth_decimals: u8,
+    pub sol_decimals: u8,
+    pub sol_voip_token_mint: Pubkey,
+    pub voip_migration_program_id: Pubkey,
+    pub sol_admin_pubkey: Pubkey,
+    pub sol_admin_keypair: &'a Keypair,
+    pub state_pda: Pubkey,
+    pub admin_ata: Pubkey,
+    pub token_program_id: Pubkey,
+    pub associated_token_program_id: Pubkey,
+    pub system_program_id: Pubkey,
+}
+
+impl Relayer<'_> {
+    /// Handles a single log from the bridge subscription.
+    pub async fn process_log(&self, log: Log) {
+        if log.topics.first() == Some(&OWNERSHIP_TRANSFERRED_TOPIC.into()) {
+            report_ownership_transferred(&log);
+
+            // a new owner may replace the relayer, so re-check roles right away
+            self.recheck_roles().await;
+            return;
+        }
+
+        self.process_lock(log).await;
+    }
+
+    /// Migrates a `TokensLocked` event to Solana and burns the locked tokens on Ethereum.
+    pub async fn process_lock(&self, log: Log) {
+        let (eth_amount, eth_address, solana_address_str) = match decode_lock(&log) {
+            Some(lock) => lock,
+            None => return,
+        };
+
+        // another instance relays this user's locks
+        if !self.partition.owns(eth_address) {
+            return;
+        }
+
+        println!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Processing New Migration
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Processing ♻️
+        "
+        );
+        let (transfer, recorded) = self.ledger.record(Transfer::new(
+            &log,
+            eth_address,
+            &solana_address_str,
+            eth_amount,
+        ));
+        let id = transfer.id;
+
+        // a redelivered or replayed lock is never relayed twice
+        if !recorded {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Lock Already Recorded
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Recorded:    {:?}
+                        Status:      Skipped⏭️
+                ",
+                transfer.status
+            );
+            return;
+        }
+        // the resume pass or a redelivered log may already be working on it
+        let _claim = match self.in_flight.claim(&id) {
+            Some(claim) => claim,
+            None => return,
+        };
+        let _permit = self.lock_permits.acquire().await;
+
+        // act only on locks enough Ethereum endpoints agree on
+        if let Err(reason) = self.confirm_lock(&log).await {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Lock Not Confirmed By Quorum
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Eth Address: {eth_address}
+                        Sol Address: {solana_address_str}
+                        Status:      Held⏸️
+                        Reason:      {reason}
+                    "
+            );
+            self.hold(&id, "quorum", reason);
+            return;
+        }
+
+        self.relay_recorded(&id, eth_address, eth_amount, &solana_address_str)
+            .await;
+    }
+
+    /// Validates, screens and admits the confirmed lock behind transfer `id`, then migrates and burns it.
+    pub async fn relay_recorded(
+        &self,
+        id: &str,
+        eth_address: H160,
+        eth_amount: U256,
+        solana_address_str: &str,
+    ) {
+        // wait while processing is halted
+        if self.until_shutdown(self.gate.wait_open()).await.is_none() {
+            return;
+        }
+
+        // validate destination, refunding locks that cannot be migrated safely
+        let solana_address = match self
+            .validate_destination(id, &eth_address, solana_address_str)
+            .await
+        {
+            Some(address) => address,
+            None => return,
+        };
+
+        // scale to SPL decimals, refunding amounts that cannot be migrated exactly
+        let amount = match convert_amount(eth_amount, self.eth_decimals, self.sol_decimals) {
+            Ok(amount) => amount,
+            Err(err) => {
+                println!(
+                    "
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Rejected Migration Amount
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Eth Address: {eth_address}
+                            Sol Address: {solana_address}
+                            Amount:      {eth_amount}
+                            Status:      Refunding ♻️
+                            Error:       {err}
+                        "
+                );
+                self.refund_lock(id, &eth_address, solana_address_str, &err.to_string())
+                    .await;
+                return;
+            }
+        };
+
+        // hold or refund transfers from screened senders or to screened destinations
+        if let Some((action, reason)) = self.screening.screen(eth_address, solana_address_str) {
+            let audit = AuditEntry {
+                at: now(),
+                actor: "screening".to_string(),
+                action: match action {
+                    ScreeningAction::Hold => Decision::Hold,
+                    ScreeningAction::Refund => Decision::Reject,
+                },
+                note: Some(reason.clone()),
+            };
+
+            if action == ScreeningAction::Refund {
+                self.ledger
+                    .update(id, |transfer| transfer.audit.push(audit));
+                println!(
+                    "
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Screened Migration Refunded
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Eth Address: {eth_address}
+                            Sol Address: {solana_address}
+                            Status:      Refunding ♻️
+                            Reason:      {reason}
+                        "
+                );
+                self.refund_lock(id, &eth_address, solana_address_str, &reason)
+                    .await;
+                return;
+            }
+
+            self.ledger.update(id, |transfer| {
+                transfer.status = TransferStatus::Held;
+                transfer.amount = Some(amount);
+                transfer.error = Some(reason.clone());
+                transfer.audit.push(audit);
+            });
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Migration Held For Approval
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Eth Address: {eth_address}
+                        Sol Address: {solana_address}
+                        Amount:      {amount}
+                        Status:      Held⏸️
+                        Reason:      {reason}
+                    "
+            );
+            return;
+        }
+
+        // hold transfers over the configured limits for manual approval
+        if let Err(err) = self.ledger.admit(id, amount, &self.settings.limits) {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Migration Held For Approval
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Eth Address: {eth_address}
+                        Sol Address: {solana_address}
+                        Amount:      {amount}
+                        Status:      Held⏸️
+                        Reason:      {err}
+                    "
+            );
+            return;
+        }
+
+        self.migrate_and_burn(id, eth_address, eth_amount, solana_address, amount)
+            .await;
+    }
+
+    /// Migrates approved and refunds rejected transfers from the held queue as decisions arrive.
+    pub async fn process_decisions(&self) {
+        while !self.shutdown.is_requested() {
+            let decided = self
+                .ledger
+                .with_status(&[TransferStatus::Approved, TransferStatus::Rejected]);
+            for transfer in decided {
+                if self.shutdown.is_requested() {
+                    return;
+                } else if transfer.status == TransferStatus::Approved {
+                    self.process_approved(transfer).await;
+                } else if refund_simulated(&transfer) {
+                    // dry run leaves the transfer rejected, so its refund is only simulated once
+                    continue;
+                } else {
+                    self.process_rejected(transfer).await;
+                }
+            }
+            self.until_shutdown(self.ledger.wait_decided()).await;
+        }
+    }
+
+    async fn process_approved(&self, transfer: Transfer) {
+        let _claim = match self.in_flight.claim(&transfer.id) {
+            Some(claim) => claim,
+            None => return,
+        };
+        let approval = decided_by(&transfer, Decision::Approve);
+        println!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                Processing Approved Migration
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Transfer:    {}
+                    Approval:    {approval}
+                    Status:      Processing ♻️
+        ",
+            transfer.id
+        );
+
+        // back to pending so a restart never picks the approval up twice
+        self.ledger.update(&transfer.id, |transfer| {
+            transfer.status = TransferStatus::Pending;
+            transfer.error = None;
+        });
+
+        // wait while processing is halted
+        if self.until_shutdown(self.gate.wait_open()).await.is_none() {
+            return;
+        }
+
+        let solana_address = match self
+            .validate_destination(&transfer.id, &transfer.eth_user, &transfer.solana_address)
+            .await
+        {
+            Some(address) => address,
+            None => return,
+        };
+        let amount = match transfer.amount {
+            Some(amount) => amount,
+            None => {
+                self.fail(&transfer.id, "approved transfer has no amount".to_string());
+                return;
+            }
+        };
+
+        self.migrate_and_burn(
+            &transfer.id,
+            transfer.eth_user,
+            transfer.eth_amount,
+            solana_address,
+            amount,
+        )
+        .await;
+    }
+
+    async fn process_rejected(&self, transfer: Transfer) {
+        let _claim = match self.in_flight.claim(&transfer.id) {
+            Some(claim) => claim,
+            None => return,
+        };
+        let rejection = decided_by(&transfer, Decision::Reject);
+        println!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                Refunding Rejected Migration
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Transfer:    {}
+                    Rejection:   {rejection}
+                    Status:      Refunding ♻️
+        ",
+            transfer.id
+        );
+
+        self.refund_lock(
+            &transfer.id,
+            &transfer.eth_user,
+            &transfer.solana_address,
+            &format!("rejected by {rejection}"),
+        )
+        .await;
+    }
+
+    /// Parses the destination of transfer `id`, refunding or failing it when invalid.
+    pub async fn validate_destination(
+        &self,
+        id: &str,
+        eth_address: &H160,
+        solana_address_str: &str,
+    ) -> Option<Pubkey> {
+        match self
+            .destination_validator
+            .validate(self.connection, solana_address_str)
+            .await
+        {
+            Ok(address) => Some(address),
+            Err(err) if err.is_refundable() => {
+                println!(
+                    "
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Rejected Solana Destination
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Eth Address: {eth_address}
+                            Sol Address: {solana_address_str}
+                            Status:      Refunding ♻️
+                            Error:       {err}
+                        "
+                );
+                self.refund_lock(id, eth_address, solana_address_str, &err.to_string())
+                    .await;
+                None
+            }
+            Err(err) => {
+                println!(
+                    "
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Failed to Validate Solana Destination
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Eth Address: {eth_address}
+                            Sol Address: {solana_address_str}
+                            Status:      Failed❌
+                            Error:       {err}
+                        "
+                );
+                self.fail(id, err.to_string());
+                None
+            }
+        }
+    }
+
+    /// Migrates `amount` to `solana_address` and burns the lock behind transfer `id`.
+    pub async fn migrate_and_burn(
+        &self,
+        id: &str,
+        eth_address: H160,
+        eth_amount: U256,
+        solana_address: Pubkey,
+        amount: u64,
+    ) {
+        // gross up amount so destination receives it in full on transfer-fee mints
+        let migrate_amount = match transfer_fee_inclusive_amount(
+            self.connection,
+            &self.sol_voip_token_mint,
+            &self.token_program_id,
+            amount,
+        )
+        .await
+        {
+            Ok(migrate_amount) => migrate_amount,
+            Err(err) => {
+                println!(
+                    "
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Failed to Calculate Transfer Fee
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Eth Address: {eth_address}
+                            Sol Address: {solana_address}
+                            Status:      Failed❌
+                            Error:       {err}
+                        "
+                );
+                self.fail(id, err.to_string());
+                return;
+            }
+        };
+
+        // simulate instead of sending when dry running
+        if self.settings.dry_run {
+            self.simulate_migrate_and_burn(id, eth_address, solana_address, migrate_amount)
+                .await;
+            return;
+        }
+
+        // wait until the admin ATA can cover this migration alongside others in flight
+        let reservation = match self
+            .until_shutdown(self.balance_guard.wait_for_voip(
+                self.connection,
+                migrate_amount,
+                self.settings.balance_check_interval,
+            ))
+            .await
+        {
+            Some(reservation) => reservation,
+            None => return,
+        };
+
+        // hold migration while the Solana leg's breaker is open
+        let attempt = match self.until_shutdown(self.sol_breaker.acquire()).await {
+            Some(attempt) => attempt,
+            None => return,
+        };
+
+        // migrate token
+        let sol_migration_hash = migrate(
+            self.connection,
+            self.program,
+            &self.state_pda,
+            &self.sol_voip_token_mint,
+            &self.voip_migration_program_id,
+            &self.sol_admin_pubkey,
+            self.sol_admin_keypair,
+            &self.admin_ata,
+            &solana_address,
+            &self.token_program_id,
+            &self.associated_token_program_id,
+            &self.system_program_id,
+            &self.settings.compute_budget,
+            &migrate_amount,
+        )
+        .await;
+        // a confirmed migrate shows in the balance from here on
+        drop(reservation);
+
+        // a program rejecting this transfer says nothing about the leg's health
+        attempt.record(matches!(
+            sol_migration_hash,
+            Ok(Ok(_)) | Ok(Err(MigrateError::Program(_)))
+        ));
+
+        let signature = match sol_migration_hash {
+            Ok(Ok(signature)) => signature,
+            Ok(Err(MigrateError::Program(err))) => {
+                self.reject_migration(id, eth_address, solana_address, err)
+                    .await;
+                return;
+            }
+            Ok(Err(MigrateError::Send(signature, err))) => {
+                match self
+                    .check_unconfirmed(
+                        id,
+                        eth_address,
+                        solana_address,
+                        signature,
+                        migrate_amount,
+                        err,
+                    )
+                    .await
+                {
+                    Some(signature) => signature,
+                    None => return,
+                }
+            }
+            Ok(Err(err)) => {
+                println!(
+                    "
+                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                  Failed to Migrate SOL VOIP Tokens
+                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                    Eth Address: {eth_address}
+                                    Sol Address: {solana_address}
+                                    Status:      Failed❌
+                                    Error Type:  Client Error
+                                    Error:       {err}
+                                "
+                );
+                self.alert("failed_migration", id, "Migration failed", &err.to_string());
+                self.fail(id, err.to_string());
+                return;
+            }
+            Err(err) => {
+                println!(
+                    "
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Failed to Migrate SOL VOIP Tokens
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Eth Address: {eth_address}
+                            Sol Address: {solana_address}
+                            Status:      Failed❌
+                            Error:       {err}
+                        "
+                );
+                self.alert("failed_migration", id, "Migration failed", &err.to_string());
+                self.fail(id, err.to_string());
+                return;
+            }
+        };
+
+        println!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                Successfully migrated SOL VOIP Token
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Eth Address:  {eth_address}
+                    SOL Address:  {solana_address}
+                    Tx Hash:      {signature}
+                    TX Status:    Success✅
+            "
+        );
+        self.ledger.update(id, |transfer| {
+            transfer.status = TransferStatus::Migrated;
+            transfer.sol_signature = Some(signature.to_string());
+            transfer.migrated_amount = Some(migrate_amount);
+            transfer.error = None;
+        });
+        // a restart must find the migration recorded rather than send it again
+        self.ledger.flush().await;
+
+        let receipt_hash = match self
+            .burn_and_verify(id, eth_address, eth_amount, solana_address)
+            .await
+        {
+            Some(receipt_hash) => receipt_hash,
+            None => return,
+        };
+        println!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Processed New Migration 💥
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        ETH Address:  {eth_address}
+                        Sol Address:  {solana_address}
+                        Amount:       {amount}
+                        Sol Tx Hash:  {signature}
+                        Eth Tx Hash:  {receipt_hash:?}
+                        Status:       Success✅
+            "
+        );
+    }
+
+    /// Burns the lock behind migrated transfer `id` and checks the receipt, returning its hash once completed.
+    pub async fn burn_and_verify(
+        &self,
+        id: &str,
+        eth_address: H160,
+        eth_amount: U256,
+        solana_address: Pubkey,
+    ) -> Option<H256> {
+        // hold burn while processing is halted or the Ethereum leg's breaker is open, leaving it to the resume pass when stepping down
+        self.until_shutdown(self.gate.wait_open()).await?;
+        let attempt = self.until_shutdown(self.eth_breaker.acquire()).await?;
+
+        // burn VOIP tokens on ethereum
+        let eth_burn_receipt = match burn(
+            self.eth_admin_private_key,
+            &self.contract,
+            eth_address,
+            &solana_address,
+        )
+        .await
+        {
+            Ok(Ok(receipt)) if receipt.status == Some(U64::zero()) => {
+                Err(format!("burn reverted in {:?}", receipt.transaction_hash))
+            }
+            Ok(Ok(receipt)) => Ok(receipt),
+            Ok(Err(err)) => Err(err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+
+        attempt.record(eth_burn_receipt.is_ok());
+
+        let receipt = match eth_burn_receipt {
+            Ok(receipt) => receipt,
+            Err(err) => {
+                println!(
+                    "
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Failed to Burn ETH VOIP tokens
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            ETH Address: {eth_address}
+                            Sol Address: {solana_address}
+                            Status:      Failed❌
+                            Error:       {err}
+                    "
+                );
+                self.ledger.update(id, |transfer| {
+                    transfer.error = Some(format!("burn failed: {err}"))
+                });
+                self.alert("failed_burn", id, "Burn failed after migration", &err);
+
+                // a reverted burn may mean the relayer role moved, so halt early
+                self.recheck_roles().await;
+                return None;
+            }
+        };
+
+        let receipt_hash = receipt.transaction_hash;
+        println!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                Successfully burnt ETH VOIP Token
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    ETH Address:  {eth_address}
+                    Sol Address:  {solana_address}
+                    Tx Hash:      {receipt_hash:?}
+                    TX Status:    Success✅
+
+            "
+        );
+
+        // confirm the bridge emitted TokensBurned for the locked user and amount
+        if let Err(err) = verify_burn(
+            &receipt,
+            self.eth_voip_bridge_address,
+            eth_address,
+            eth_amount,
+        ) {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Burn Reconciliation Failed
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        ETH Address:  {eth_address}
+                        Sol Address:  {solana_address}
+                        Eth Tx Hash:  {receipt_hash:?}
+                        Status:       Failed❌
+                        Error:        {err}
+                "
+            );
+            self.ledger.update(id, |transfer| {
+                transfer.status = TransferStatus::ReconciliationFailed;
+                transfer.eth_burn_tx_hash = Some(receipt_hash);
+                transfer.error = Some(err.clone());
+            });
+            self.alert(
+                "failed_burn",
+                id,
+                "Burn reconciliation failed",
+                &format!("{err}\nEth Tx Hash: {receipt_hash:?}"),
+            );
+            return None;
+        }
+
+        self.ledger.update(id, |transfer| {
+            transfer.status = TransferStatus::Completed;
+            transfer.eth_burn_tx_hash = Some(receipt_hash);
+            transfer.error = None;
+        });
+        Some(receipt_hash)
+    }
+
+    /// Refunds or holds transfer `id` after the migration program rejected it in simulation.
+    async fn reject_migration(
+        &self,
+        id: &str,
+        eth_address: H160,
+        solana_address: Pubkey,
+        err: ProgramError,
+    ) {
+        let refund =
+            err.disposition(&self.settings.migrate_refund_error_codes) == Disposition::Refund;
+        println!(
+            "
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Migration Rejected By Program
+                        ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Eth Address: {eth_address}
+                            Sol Address: {solana_address}
+                            Status:      {}
+                            Error:       {err}
+                        ",
+            if refund {
+                "Refunding ♻️"
+            } else {
+                "Held⏸️"
+            }
+        );
+        let reason = format!("migration program error {err}");
+        self.alert(
+            "failed_migration",
+            id,
+            "Migration rejected by program",
+            &reason,
+        );
+
+        if refund {
+            self.refund_lock(id, &eth_address, &solana_address.to_string(), &reason)
+                .await;
+            return;
+        }
+
+        self.hold(id, "simulation", reason);
+    }
+
+    /// Looks up a migrate transaction whose confirmation failed, returning its signature when it landed anyway.
+    async fn check_unconfirmed(
+        &self,
+        id: &str,
+        eth_address: H160,
+        solana_address: Pubkey,
+        signature: Signature,
+        migrate_amount: u64,
+        err: Box<ClientError>,
+    ) -> Option<Signature> {
+        let status = self.connection.get_signature_status(&signature).await;
+        if let Ok(Some(Ok(()))) = status {
+            return Some(signature);
+        }
+
+        if let Ok(Some(Err(tx_err))) = status {
+            println!(
+                "
+                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                  Failed to Migrate SOL VOIP Tokens
+                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                    Eth Address: {eth_address}
+                                    Sol Address: {solana_address}
+                                    Tx Hash:     {signature}
+                                    Status:      Failed❌
+                                    Error:       {tx_err}
+                                "
+            );
+            let error = format!("migrate transaction {signature} failed: {tx_err}");
+            self.alert("failed_migration", id, "Migration failed", &error);
+            self.fail(id, error);
+            return None;
+        }
+
+        // not found yet: the transaction may still land, so the transfer stays pending with its signature
+        let error = format!("migrate transaction {signature} unconfirmed: {err}");
+        println!(
+            "
+                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                    SOL Migration Unconfirmed
+                                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                                    Eth Address: {eth_address}
+                                    Sol Address: {solana_address}
+                                    Tx Hash:     {signature}
+                                    Status:      Processing ♻️
+                                    Error:       {err}
+                                "
+        );
+        self.alert("unconfirmed_migration", id, "Migration unconfirmed", &error);
+        self.ledger.update(id, |transfer| {
+            transfer.sol_signature = Some(signature.to_string());
+            transfer.migrated_amount = Some(migrate_amount);
+            transfer.error = Some(error);
+        });
+        None
+    }
+
+    /// Runs `wait` unless shutdown is requested first, so a transfer waiting to send stops where it is.
+    pub async fn until_shutdown<T>(&self, wait: impl std::future::Future<Output = T>) -> Option<T> {
+        tokio::select! {
+            biased;
+            result = wait => Some(result),
+            _ = self.shutdown.requested() => None,
+        }
+    }
+
+    /// Holds transfer `id` for manual approval, recording `actor` and `reason` in its audit trail.
+    pub fn hold(&self, id: &str, actor: &str, reason: String) {
+        self.ledger.update(id, |transfer| {
+            transfer.status = TransferStatus::Held;
+            transfer.error = Some(reason.clone());
+            transfer.audit.push(AuditEntry {
+                at: now(),
+                actor: actor.to_string(),
+                action: Decision::Hold,
+                note: Some(reason),
+            });
+        });
+    }
+
+    /// Checks that at least the configured quorum of Ethereum endpoints return the lock in `log`.
+    pub async fn confirm_lock(&self, log: &Log) -> Result<(), String> {
+        let quorum = self.settings.eth_lock_quorum;
+        if quorum <= 1 {
+            return Ok(());
+        }
+
+        let mut confirmations = 0;
+        for attempt in 0..QUORUM_ATTEMPTS {
+            if attempt > 0 {
+                tokio::time::sleep(QUORUM_RETRY).await;
+            }
+            confirmations = self.eth_transport.confirmations(log).await;
+            if confirmations >= quorum {
+                return Ok(());
+            }
+        }
+        Err(format!(
+            "lock confirmed by {confirmations} of {} endpoints, {quorum} required",
+            self.eth_transport.endpoints().urls.len()
+        ))
+    }
+
+    /// Returns the locked tokens for transfer `id` to the user through `unlockTokens`.
+    async fn refund_lock(&self, id: &str, eth_address: &H160, solana_address: &str, reason: &str) {
+        if self.settings.dry_run {
+            self.simulate_refund(id, *eth_address, solana_address).await;
+            return;
+        }
+
+        // hold refund while the Ethereum leg's breaker is open, leaving it to the next leader when stepping down
+        let attempt = match self.until_shutdown(self.eth_breaker.acquire()).await {
+            Some(attempt) => attempt,
+            None => return,
+        };
+        let refund_receipt = match refund(
+            self.eth_admin_private_key,
+            &self.contract,
+            eth_address,
+            solana_address,
+        )
+        .await
+        {
+            Ok(Ok(receipt)) if receipt.status == Some(U64::zero()) => {
+                Err(format!("refund reverted in {:?}", receipt.transaction_hash))
+            }
+            Ok(Ok(receipt)) => Ok(receipt),
+            Ok(Err(err)) => Err(err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+
+        attempt.record(refund_receipt.is_ok());
+
+        match refund_receipt {
+            Ok(receipt) => {
+                let receipt_hash = receipt.transaction_hash;
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Refunded Locked ETH VOIP Tokens
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        ETH Address:  {eth_address}
+                        Sol Address:  {solana_address}
+                        Tx Hash:      {receipt_hash:?}
+                        TX Status:    Success✅
+                "
+                );
+                self.ledger.update(id, |transfer| {
+                    transfer.status = TransferStatus::Refunded;
+                    transfer.eth_refund_tx_hash = Some(receipt_hash);
+                    transfer.error = Some(reason.to_string());
+                });
+                self.alert(
+                    "refund",
+                    id,
+                    "Lock refunded",
+                    &format!("{reason}\nEth Tx Hash: {receipt_hash:?}"),
+                );
+            }
+            Err(err) => {
+                println!(
+                    "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Failed to Refund ETH VOIP Tokens
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    ETH Address: {eth_address}
+                    Sol Address: {solana_address}
+                    Status:      Failed❌
+                    Error:       {err}
+            "
+                );
+                self.alert(
+                    "failed_refund",
+                    id,
+                    "Refund failed",
+                    &format!("{reason}; refund failed: {err}"),
+                );
+                self.fail(id, format!("{reason}; refund failed: {err}"));
+            }
+        }
+    }
+
+    /// Marks transfer `id` as failed with `error`.
+    pub fn fail(&self, id: &str, error: String) {
+        self.ledger.update(id, |transfer| {
+            transfer.status = TransferStatus::Failed;
+            transfer.error = Some(error);
+        });
+    }
+
+    /// Notifies operators about an incident on transfer `id`.
+    pub fn alert(&self, kind: &'static str, id: &str, title: &str, detail: &str) {
+        let transfer = self.ledger.get(id);
+        let (eth_user, solana_address) = transfer
+            .map(|transfer| (format!("{:?}", transfer.eth_user), transfer.solana_address))
+            .unwrap_or_default();
+        self.notifier.notify(Incident {
+            kind,
+            subject: id.to_string(),
+            title: format!("{title}: {id}"),
+            detail: format!("{detail}\nETH Address: {eth_user}\nSol Address: {solana_address}"),
+        });
+    }
+
+    async fn recheck_roles(&self) {
+        recheck_roles(
+            self.gate,
+            &self.contract,
+            self.program,
+            &self.state_pda,
+            self.eth_relayer,
+            self.sol_admin_pubkey,
+        )
+        .await;
+    }
+}
+
+/// Describes the latest `decision` on `transfer` as "actor (note)".
+fn decided_by(transfer: &Transfer, decision: Decision) -> String {
+    match transfer
+        .audit
+        .iter()
+        .rev()
+        .find(|entry| entry.action == decision)
+    {
+        Some(entry) => match &entry.note {
+            Some(note) => format!("{} ({note})", entry.actor),
+            None => entry.actor.clone(),
+        },
+        None => "unknown".to_string(),
+    }
+}
+
+/// Simulates, budgets and sends the migrate transaction, creating the destination ATA when missing.
+pub async fn migrate(
+    connection: &RpcClient,
+    program: &anchor_client::Program<Arc<Keypair>>,
+    state_pda: &Pubkey,
+    sol_voip_token_mint: &Pubkey,
+    sol_voip_migration_program_id: &Pubkey,
+    sol_admin_pubkey: &Pubkey,
+    sol_admin_keypair: &Keypair,
+    sol_admin_ata: &Pubkey,
+    solana_address: &Pubkey,
+    token_program_id: &Pubkey,
+    associated_token_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    compute_budget: &ComputeBudget,
+    amount: &u64,
+) -> Result<Result<Signature, MigrateError>, Box<dyn std::error::Error>> {
+    let instructions = migrate_instructions(
+        connection,
+        program,
+        state_pda,
+        sol_voip_token_mint,
+        sol_voip_migration_program_id,
+        sol_admin_pubkey,
+        sol_admin_ata,
+        solana_address,
+        token_program_id,
+        associated_token_program_id,
+        system_program_id,
+        *amount,
+    )
+    .await?;
+
+    // simulate first so program errors surface before fees are paid
+    let simulation = simulate(connection, sol_admin_keypair, compute_budget, &instructions).await?;
+    if let Some(err) = &simulation.err {
+        if let Some(program_error) = program_error(&simulation) {
+            return Ok(Err(MigrateError::Program(program_error)));
+        }
+        let logs = simulation.logs.clone().unwrap_or_default();
+        let tail = logs[logs.len().saturating_sub(5)..].join(" | ");
+        return Ok(Err(MigrateError::Simulation(format!(
+            "{err}; logs: {tail}"
+        ))));
+    }
+
+    // request the simulated compute units plus headroom
+    let mut budgeted = compute_budget_instructions(compute_budget, simulation.units_consumed);
+    budgeted.extend(instructions);
+    let transaction = sign(connection, sol_admin_keypair, &budgeted).await?;
+    let signature = transaction.signatures[0];
+
+    Ok(connection
+        .send_and_confirm_transaction(&transaction)
+        .await
+        .map_err(|err| MigrateError::Send(signature, Box::new(err))))
+}
+
+/// Instructions creating the destination ATA when it does not exist, then migrating `amount` to it.
+pub async fn migrate_instructions(
+    connection: &RpcClient,
+    program: &anchor_client::Program<Arc<Keypair>>,
+    state_pda: &Pubkey,
+    sol_voip_token_mint: &Pubkey,
+    sol_voip_migration_program_id: &Pubkey,
+    sol_admin_pubkey: &Pubkey,
+    sol_admin_ata: &Pubkey,
+    solana_address: &Pubkey,
+    token_program_id: &Pubkey,
+    associated_token_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    amount: u64,
+) -> Result<Vec<Instruction>, Box<dyn std::error::Error>> {
+    let (destination_ata, _) = Pubkey::find_program_address(
+        &[
+            solana_address.as_ref(),
+            token_program_id.as_ref(),
+            sol_voip_token_mint.as_ref(),
+        ],
+        associated_token_program_id,
+    );
+    let (migration_pda, _) = Pubkey::find_program_address(
+        &[&b"migration"[..], solana_address.as_ref()],
+        sol_voip_migration_program_id,
+    );
+
+    let mut instructions = Vec::new();
+    if connection.get_account(&destination_ata).await.is_err() {
+        instructions.push(Instruction {
+            program_id: *associated_token_program_id,
+            accounts: vec![
+                AccountMeta::new(*sol_admin_pubkey, true),
+                AccountMeta::new(destination_ata, false),
+                AccountMeta::new_readonly(*solana_address, false),
+                AccountMeta::new_readonly(*sol_voip_token_mint, false),
+                AccountMeta::new_readonly(*system_program_id, false),
+                AccountMeta::new_readonly(*token_program_id, false),
+            ],
+            data: vec![0],
+        });
+    }
+
+    instructions.extend(
+        program
+            .request()
+            .accounts(voip_migration::accounts::Migrate {
+                migration: migration_pda,
+                state: *state_pda,
+                destination_ata,
+                admin_ata: *sol_admin_ata,
+                admin: *sol_admin_pubkey,
+                destination: *solana_address,
+                mint: *sol_voip_token_mint,
+                token_program: *token_program_id,
+                system_program: *system_program_id,
+                associated_token_program: *associated_token_program_id,
+            })
+            .args(voip_migration::instruction::Migrate { amount })
+            .instructions()?,
+    );
+    Ok(instructions)
+}
+
+pub async fn refund(
+    eth_admin_private_key: &signing::SecretKey,
+    contract: &Contract<FailoverHttp>,
+    ethereum_address: &H160,
+    solana_address: &str,
+) -> Result<Result<web3::types::TransactionReceipt, web3::Error>, Box<dyn std::error::Error>> {
+    let refund_transaction_receipt = contract
+        .signed_call_with_confirmations(
+            "unlockTokens",
+            (*ethereum_address, solana_address.to_string()),
+            Options::default(),
+            1,
+            eth_admin_private_key,
+        )
+        .await;
+
+    Ok(refund_transaction_receipt)
+}
+
+pub async fn transfer_fee_inclusive_amount(
+    connection: &RpcClient,
+    sol_voip_token_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    // only Token-2022 mints can carry a transfer fee
+    if *token_program_id != spl_token_2022::ID {
+        return Ok(amount);
+    }
+
+    let mint_account = connection.get_account(sol_voip_token_mint).await?;
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_account.data)?;
+    let transfer_fee_config = match mint.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config,
+        Err(_) => return Ok(amount),
+    };
+
+    // fee schedule can change per epoch
+    let epoch = connection.get_epoch_info().await?.epoch;
+    let fee = transfer_fee_config
+        .get_epoch_fee(epoch)
+        .calculate_inverse_fee(amount)
+        .ok_or("Failed to calculate transfer fee")?;
+
+    Ok(amount
+        .checked_add(fee)
+        .ok_or("Transfer fee overflows amount")?)
+}
+
+pub async fn burn(
+    eth_admin_private_key: &signing::SecretKey,
+    contract: &Contract<FailoverHttp>,
+    ethereum_address: H160,
+    solana_address: &Pubkey,
+) -> Result<Result<web3::types::TransactionReceipt, web3::Error>, Box<dyn std::error::Error>> {
+    let burn_transaction_receipt = contract
+        .signed_call_with_confirmations(
+            "burnTokens",
+            (ethereum_address, solana_address.to_string()),
+            Options::default(),
+            1,
+            eth_admin_private_key,
+        )
+        .await;
+
+    Ok(burn_transaction_receipt)
+}
@@ -0,0 +1,372 @@
+use crate::amount::convert_amount;
+use crate::bridge::{decode_lock, TOKENS_LOCKED_TOPIC};
+use crate::endpoints::FailoverHttp;
+use crate::ledger::{now, transfer_id, AuditEntry, Decision, Transfer, TransferStatus};
+use crate::partition::Partition;
+use crate::relay::{transfer_fee_inclusive_amount, Relayer};
+use crate::screening::ScreeningAction;
+use std::str::FromStr;
+use web3::types::{Log, H256, U64};
+
+/// Arguments of `replay <eth_tx_hash> [--dry-run] [--partition <index>]`.
+pub struct Replay {
+    pub tx_hash: H256,
+    /// Report what would be sent instead of sending it.
+    pub dry_run: bool,
+    /// Partition whose lease and ledger the replay uses.
+    pub partition: u64,
+}
+
+impl Replay {
+    pub fn from_args(args: &[String]) -> Self {
+        let usage = "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Usage: voip-relayer-rs replay <eth_tx_hash> [--dry-run] [--partition <index>]
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        ";
+
+        let (tx_hash, mut flags) = match args {
+            [tx_hash, flags @ ..] => (tx_hash, flags.iter()),
+            _ => panic!("{usage}"),
+        };
+        let mut replay = Self {
+            tx_hash: H256::from_str(tx_hash).unwrap_or_else(|_| panic!("{usage}")),
+            dry_run: false,
+            partition: 0,
+        };
+        while let Some(flag) = flags.next() {
+            match flag.as_str() {
+                "--dry-run" => replay.dry_run = true,
+                "--partition" => {
+                    replay.partition = flags
+                        .next()
+                        .and_then(|index| index.parse().ok())
+                        .unwrap_or_else(|| panic!("{usage}"))
+                }
+                _ => panic!("{usage}"),
+            }
+        }
+        replay
+    }
+}
+
+/// Whether replaying picks up a transfer recorded as `status`; settled ones are left alone.
+fn resumable(status: TransferStatus) -> bool {
+    matches!(
+        status,
+        TransferStatus::Pending | TransferStatus::Migrated | TransferStatus::Failed
+    )
+}
+
+/// Returns a failed `transfer`, which sent nothing, to pending, keeping its error in the audit trail.
+fn retry_failed(transfer: &mut Transfer) {
+    if transfer.status != TransferStatus::Failed {
+        return;
+    }
+    transfer.audit.push(AuditEntry {
+        at: now(),
+        actor: "replay".to_string(),
+        action: Decision::Annotate,
+        note: transfer
+            .error
+            .take()
+            .map(|error| format!("failed: {error}")),
+    });
+    transfer.status = TransferStatus::Pending;
+}
+
+impl Relayer<'_> {
+    /// Runs the `TokensLocked` events of one Ethereum transaction through the relay pipeline.
+    pub async fn replay(
+        &self,
+        eth: &web3::api::Eth<FailoverHttp>,
+        replay: &Replay,
+    ) -> Result<(), String> {
+        let receipt = eth
+            .transaction_receipt(replay.tx_hash)
+            .await
+            .map_err(|err| err.to_string())?
+            .ok_or(format!("transaction {:?} not found", replay.tx_hash))?;
+        if receipt.status == Some(U64::zero()) {
+            return Err(format!("transaction {:?} reverted", replay.tx_hash));
+        }
+
+        let locks: Vec<Log> = receipt
+            .logs
+            .into_iter()
+            .filter(|log| {
+                log.address == self.eth_voip_bridge_address
+                    && log.topics.first() == Some(&TOKENS_LOCKED_TOPIC.into())
+            })
+            .collect();
+        if locks.is_empty() {
+            return Err(format!(
+                "transaction {:?} has no TokensLocked events from the bridge",
+                replay.tx_hash
+            ));
+        }
+
+        for log in locks {
+            let id = transfer_id(&log);
+
+            // each partition's locks live in that partition's ledger
+            if let Some((_, eth_address, _)) = decode_lock(&log) {
+                if !self.partition.owns(eth_address) {
+                    println!(
+                        "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Lock In Another Partition
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Partition:   {}
+                        Status:      Skipped⏭️
+                ",
+                        Partition::of(eth_address, self.partition.count)
+                    );
+                    continue;
+                }
+            }
+
+            // stuck and failed transfers are resumed, settled ones left alone
+            if let Some(transfer) = self.ledger.get(&id) {
+                let resumable = resumable(transfer.status);
+                if resumable && replay.dry_run {
+                    println!(
+                        "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Replay Dry Run
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Recorded:    {:?}
+                        Would:       resume from {:?}
+                        Error:       {}
+                ",
+                        transfer.status,
+                        transfer.status,
+                        transfer.error.unwrap_or_default()
+                    );
+                    continue;
+                }
+                if resumable {
+                    if transfer.status == TransferStatus::Failed {
+                        self.ledger.update(&id, retry_failed);
+                    }
+                    if let Some(transfer) = self.ledger.get(&id) {
+                        self.resume(transfer).await;
+                    }
+                    self.report_replayed(&id);
+                    continue;
+                }
+
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Lock Already Recorded
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Recorded:    {:?}
+                        Sol Tx Hash: {}
+                        Status:      Skipped⏭️
+                ",
+                    transfer.status,
+                    transfer.sol_signature.unwrap_or_default()
+                );
+                continue;
+            }
+
+            if replay.dry_run {
+                self.preview_lock(&id, &log).await;
+                continue;
+            }
+
+            self.process_lock(log).await;
+            self.report_replayed(&id);
+        }
+        Ok(())
+    }
+
+    /// Prints where replaying transfer `id` left it.
+    fn report_replayed(&self, id: &str) {
+        if let Some(transfer) = self.ledger.get(id) {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Replayed Lock
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Recorded:    {:?}
+                        Sol Tx Hash: {}
+                        Eth Tx Hash: {:?}
+                        Error:       {}
+                ",
+                transfer.status,
+                transfer.sol_signature.unwrap_or_default(),
+                transfer.eth_burn_tx_hash.or(transfer.eth_refund_tx_hash),
+                transfer.error.unwrap_or_default()
+            );
+        }
+    }
+
+    /// Reports what relaying the lock in `log` would do, without recording or sending anything.
+    async fn preview_lock(&self, id: &str, log: &Log) {
+        let (eth_amount, eth_address, solana_address_str) = match decode_lock(log) {
+            Some(lock) => lock,
+            None => return,
+        };
+
+        let outcome = match self
+            .destination_validator
+            .validate(self.connection, &solana_address_str)
+            .await
+        {
+            Err(err) if err.is_refundable() => format!("refund: {err}"),
+            Err(err) => format!("fail: {err}"),
+            Ok(solana_address) => {
+                match convert_amount(eth_amount, self.eth_decimals, self.sol_decimals) {
+                    Err(err) => format!("refund: {err}"),
+                    Ok(amount) => match self.screening.screen(eth_address, &solana_address_str) {
+                        Some((ScreeningAction::Refund, reason)) => format!("refund: {reason}"),
+                        Some((ScreeningAction::Hold, reason)) => format!("hold: {reason}"),
+                        None => match self.ledger.check_limits(
+                            eth_address,
+                            amount,
+                            &self.settings.limits,
+                        ) {
+                            Err(err) => format!("hold: {err}"),
+                            Ok(()) => match transfer_fee_inclusive_amount(
+                                self.connection,
+                                &self.sol_voip_token_mint,
+                                &self.token_program_id,
+                                amount,
+                            )
+                            .await
+                            {
+                                Ok(migrate_amount) => format!(
+                                    "migrate {migrate_amount} to {solana_address}, then burn {eth_amount}"
+                                ),
+                                Err(err) => format!("fail: {err}"),
+                            },
+                        },
+                    },
+                }
+            }
+        };
+
+        let halted_by = self.gate.reasons().join(", ");
+        println!(
+            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Replay Dry Run
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Eth Address: {eth_address}
+                        Sol Address: {solana_address_str}
+                        Amount:      {eth_amount}
+                        Would:       {outcome}
+                        Halted By:   {halted_by}
+                "
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::tests::lock_log;
+    use web3::types::{H160, U256};
+
+    const TX: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn transfer(status: TransferStatus, error: Option<&str>) -> Transfer {
+        let user = H160::repeat_byte(0x42);
+        let log = lock_log(U256::from(5), user, "dest");
+        Transfer {
+            status,
+            error: error.map(String::from),
+            ..Transfer::new(&log, user, "dest", U256::from(5))
+        }
+    }
+
+    #[test]
+    fn parses_the_hash_and_flags_in_any_order() {
+        let replay = Replay::from_args(&args(&[TX]));
+        assert_eq!(replay.tx_hash, H256::repeat_byte(0x11));
+        assert!(!replay.dry_run);
+        assert_eq!(replay.partition, 0);
+
+        let replay = Replay::from_args(&args(&[TX, "--partition", "3", "--dry-run"]));
+        assert!(replay.dry_run);
+        assert_eq!(replay.partition, 3);
+
+        let replay = Replay::from_args(&args(&[TX, "--dry-run", "--partition", "1"]));
+        assert!(replay.dry_run);
+        assert_eq!(replay.partition, 1);
+    }
+
+    #[test]
+    fn rejects_malformed_arguments() {
+        for bad in [
+            &[][..],
+            &["0x12"],
+            &["--dry-run", TX],
+            &[TX, "--partition"],
+            &[TX, "--partition", "-1"],
+            &[TX, "--force"],
+        ] {
+            let parsed = std::panic::catch_unwind(|| Replay::from_args(&args(bad)));
+            assert!(parsed.is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn only_unsettled_transfers_are_resumed() {
+        for status in [
+            TransferStatus::Pending,
+            TransferStatus::Migrated,
+            TransferStatus::Failed,
+        ] {
+            assert!(resumable(status), "{status:?}");
+        }
+        for status in [
+            TransferStatus::Held,
+            TransferStatus::Approved,
+            TransferStatus::Rejected,
+            TransferStatus::Completed,
+            TransferStatus::Refunded,
+            TransferStatus::ReconciliationFailed,
+        ] {
+            assert!(!resumable(status), "{status:?}");
+        }
+    }
+
+    #[test]
+    fn a_failed_transfer_is_retried_as_pending() {
+        let mut failed = transfer(TransferStatus::Failed, Some("rpc timed out"));
+        retry_failed(&mut failed);
+
+        assert_eq!(failed.status, TransferStatus::Pending);
+        assert_eq!(failed.error, None);
+        assert_eq!(failed.audit.len(), 1);
+        assert_eq!(failed.audit[0].actor, "replay");
+        assert_eq!(failed.audit[0].action, Decision::Annotate);
+        assert_eq!(
+            failed.audit[0].note.as_deref(),
+            Some("failed: rpc timed out")
+        );
+    }
+
+    #[test]
+    fn other_transfers_are_not_retried() {
+        let mut migrated = transfer(TransferStatus::Migrated, Some("burn reverted"));
+        retry_failed(&mut migrated);
+
+        assert_eq!(migrated.status, TransferStatus::Migrated);
+        assert_eq!(migrated.error.as_deref(), Some("burn reverted"));
+        assert!(migrated.audit.is_empty());
+    }
+}
@@ -0,0 +1,428 @@
+use crate::ledger::{now, Transfer, TransferStatus};
+use crate::reconcile::{signatures_for_address, token_balance_change};
+use crate::relay::Relayer;
+use anchor_client::solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use web3::contract::Options;
+use web3::types::{Log, U256};
+
+// a migrate signed with a blockhash this old can no longer land
+const BLOCKHASH_EXPIRY: Duration = Duration::from_secs(120);
+
+// how far the relayer clock may run ahead of Solana block times
+const CLOCK_SKEW_SECS: u64 = 300;
+
+/// Transfers this instance is working on, so the resume pass leaves them alone.
+#[derive(Default)]
+pub struct InFlight {
+    ids: Mutex<HashSet<String>>,
+}
+
+impl InFlight {
+    /// Marks transfer `id` in flight until the claim is dropped, or `None` when it already is.
+    pub fn claim(&self, id: &str) -> Option<Claim<'_>> {
+        self.ids
+            .lock()
+            .unwrap()
+            .insert(id.to_string())
+            .then(|| Claim {
+                in_flight: self,
+                id: id.to_string(),
+            })
+    }
+}
+
+/// A transfer in flight, released when dropped.
+pub struct Claim<'a> {
+    in_flight: &'a InFlight,
+    id: String,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        self.in_flight.ids.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl Relayer<'_> {
+    /// Resumes stuck transfers on startup and every `resume_interval` after, until shutdown.
+    pub async fn resume_transfers(&self) {
+        while !self.shutdown.is_requested() {
+            self.resume_stuck().await;
+            self.until_shutdown(tokio::time::sleep(self.settings.resume_interval))
+                .await;
+        }
+    }
+
+    /// Resumes pending and migrated transfers nothing in this instance has touched for `resume_after`.
+    pub async fn resume_stuck(&self) {
+        // dry run leaves stuck transfers to the live relayer
+        if self.settings.dry_run {
+            return;
+        }
+
+        let idle = self.settings.resume_after.max(BLOCKHASH_EXPIRY);
+        let touched_before = now().saturating_sub(idle.as_secs());
+        let stuck = self.ledger.find(|transfer| {
+            matches!(
+                transfer.status,
+                TransferStatus::Pending | TransferStatus::Migrated
+            ) && transfer.updated_at <= touched_before
+        });
+        for transfer in stuck {
+            if self.shutdown.is_requested() {
+                return;
+            }
+            self.resume(transfer).await;
+        }
+    }
+
+    /// Picks transfer up from its recorded status, checking Solana before anything is migrated again.
+    pub async fn resume(&self, transfer: Transfer) {
+        let _claim = match self.in_flight.claim(&transfer.id) {
+            Some(claim) => claim,
+            None => return,
+        };
+        println!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                  Resuming Stuck Transfer
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Transfer:    {}
+                    Recorded:    {:?}
+                    Sol Tx Hash: {}
+                    Status:      Resumed▶️
+        ",
+            transfer.id,
+            transfer.status,
+            transfer.sol_signature.clone().unwrap_or_default()
+        );
+
+        match (transfer.status, &transfer.sol_signature, transfer.amount) {
+            (TransferStatus::Migrated, _, _) => self.resume_burn(&transfer).await,
+            (_, Some(signature), _) => self.resume_sent(&transfer, signature).await,
+            (_, None, Some(amount)) => self.resume_admitted(&transfer, amount).await,
+            (_, None, None) => self.resume_recorded(&transfer).await,
+        }
+    }
+
+    /// Burns the lock behind a migrated transfer unless the bridge no longer holds it.
+    async fn resume_burn(&self, transfer: &Transfer) {
+        let solana_address = match self.destination(transfer) {
+            Some(address) => address,
+            None => return,
+        };
+
+        // a burn that landed without being recorded leaves nothing to burn
+        let locked: U256 = match self
+            .contract
+            .query(
+                "lockedBalances",
+                (transfer.eth_user, transfer.solana_address.clone()),
+                None,
+                Options::default(),
+                None,
+            )
+            .await
+        {
+            Ok(locked) => locked,
+            Err(err) => {
+                self.report_unresumed(&transfer.id, &err.to_string());
+                return;
+            }
+        };
+        if locked.is_zero() {
+            let error = "lock no longer held by the bridge, burn not recorded".to_string();
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Burn Reconciliation Failed
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:     {}
+                        Status:       Failed❌
+                        Error:        {error}
+                ",
+                transfer.id
+            );
+            self.ledger.update(&transfer.id, |transfer| {
+                transfer.status = TransferStatus::ReconciliationFailed;
+                transfer.error = Some(error.clone());
+            });
+            self.alert(
+                "failed_burn",
+                &transfer.id,
+                "Burn reconciliation failed",
+                &error,
+            );
+            return;
+        }
+
+        if let Some(receipt_hash) = self
+            .burn_and_verify(
+                &transfer.id,
+                transfer.eth_user,
+                transfer.eth_amount,
+                solana_address,
+            )
+            .await
+        {
+            self.report_resumed(transfer, &format!("{receipt_hash:?}"));
+        }
+    }
+
+    /// Settles a transfer whose migrate was sent but never confirmed, migrating again once it can no longer land.
+    async fn resume_sent(&self, transfer: &Transfer, signature: &str) {
+        let signature = match Signature::from_str(signature) {
+            Ok(signature) => signature,
+            Err(err) => {
+                self.fail(&transfer.id, format!("invalid migrate signature: {err}"));
+                return;
+            }
+        };
+
+        let status = self
+            .connection
+            .get_signature_status_with_commitment_and_history(
+                &signature,
+                self.connection.commitment(),
+                true,
+            )
+            .await;
+        let error = match status {
+            Ok(Some(Ok(()))) => {
+                self.ledger.update(&transfer.id, |transfer| {
+                    transfer.status = TransferStatus::Migrated;
+                    transfer.error = None;
+                });
+                self.ledger.flush().await;
+                if let Some(transfer) = self.ledger.get(&transfer.id) {
+                    self.resume_burn(&transfer).await;
+                }
+                return;
+            }
+            Ok(Some(Err(tx_err))) => format!("migrate transaction {signature} failed: {tx_err}"),
+            // left untouched past the blockhash expiry, so it was dropped
+            Ok(None) => format!("migrate transaction {signature} expired"),
+            Err(err) => {
+                self.report_unresumed(&transfer.id, &err.to_string());
+                return;
+            }
+        };
+
+        // nothing was sent out of the admin ATA, so the transfer is migrated again
+        self.ledger.update(&transfer.id, |transfer| {
+            transfer.sol_signature = None;
+            transfer.migrated_amount = None;
+            transfer.error = Some(error);
+        });
+        match transfer.amount {
+            Some(amount) => self.resume_admitted(transfer, amount).await,
+            None => self.fail(&transfer.id, "sent transfer has no amount".to_string()),
+        }
+    }
+
+    /// Migrates an admitted transfer with no recorded migrate, unless Solana shows one landed.
+    async fn resume_admitted(&self, transfer: &Transfer, amount: u64) {
+        let solana_address = match self.destination(transfer) {
+            Some(address) => address,
+            None => return,
+        };
+
+        match self.find_migration(transfer, &solana_address, amount).await {
+            Ok(Some((signature, migrated_amount))) => {
+                self.ledger.update(&transfer.id, |transfer| {
+                    transfer.status = TransferStatus::Migrated;
+                    transfer.sol_signature = Some(signature.to_string());
+                    transfer.migrated_amount = Some(migrated_amount);
+                    transfer.error = None;
+                });
+                self.ledger.flush().await;
+                if let Some(transfer) = self.ledger.get(&transfer.id) {
+                    self.resume_burn(&transfer).await;
+                }
+            }
+            Ok(None) => {
+                // wait while processing is halted
+                if self.until_shutdown(self.gate.wait_open()).await.is_none() {
+                    return;
+                }
+                self.migrate_and_burn(
+                    &transfer.id,
+                    transfer.eth_user,
+                    transfer.eth_amount,
+                    solana_address,
+                    amount,
+                )
+                .await;
+            }
+            Err(err) => self.report_unresumed(&transfer.id, &err.to_string()),
+        }
+    }
+
+    /// Runs a transfer recorded before it was admitted through the pipeline from its lock again.
+    async fn resume_recorded(&self, transfer: &Transfer) {
+        let log = match self.recorded_lock(transfer).await {
+            Ok(Some(log)) => log,
+            Ok(None) => {
+                self.hold(
+                    &transfer.id,
+                    "resume",
+                    "lock no longer found on Ethereum".to_string(),
+                );
+                return;
+            }
+            Err(err) => {
+                self.report_unresumed(&transfer.id, &err.to_string());
+                return;
+            }
+        };
+
+        if let Err(reason) = self.confirm_lock(&log).await {
+            self.hold(&transfer.id, "quorum", reason);
+            return;
+        }
+
+        self.relay_recorded(
+            &transfer.id,
+            transfer.eth_user,
+            transfer.eth_amount,
+            &transfer.solana_address,
+        )
+        .await;
+    }
+
+    /// A successful migration of `amount` to `solana_address` since the transfer was recorded and not credited to any other transfer.
+    async fn find_migration(
+        &self,
+        transfer: &Transfer,
+        solana_address: &Pubkey,
+        amount: u64,
+    ) -> Result<Option<(Signature, u64)>, Box<dyn std::error::Error>> {
+        let (migration_pda, _) = Pubkey::find_program_address(
+            &[&b"migration"[..], solana_address.as_ref()],
+            &self.voip_migration_program_id,
+        );
+        let since = transfer.created_at.saturating_sub(CLOCK_SKEW_SECS);
+
+        // newest first, so stop at the first migration older than the transfer
+        for status in signatures_for_address(self.connection, &migration_pda, None, None).await? {
+            if status
+                .block_time
+                .is_some_and(|block_time| (block_time as u64) < since)
+            {
+                break;
+            }
+            let credited = !self
+                .ledger
+                .find(|other| other.sol_signature.as_deref() == Some(status.signature.as_str()))
+                .is_empty();
+            if status.err.is_some() || credited {
+                continue;
+            }
+
+            let signature = Signature::from_str(&status.signature)?;
+            let received = token_balance_change(
+                self.connection,
+                &signature,
+                &self.sol_voip_token_mint,
+                solana_address,
+            )
+            .await?;
+            if received != Some(amount as i128) {
+                continue;
+            }
+            let sent = token_balance_change(
+                self.connection,
+                &signature,
+                &self.sol_voip_token_mint,
+                &self.sol_admin_pubkey,
+            )
+            .await?
+            .unwrap_or_default();
+            return Ok(Some((signature, sent.unsigned_abs() as u64)));
+        }
+        Ok(None)
+    }
+
+    /// The `TokensLocked` log behind transfer, or `None` when its transaction no longer carries it.
+    async fn recorded_lock(&self, transfer: &Transfer) -> Result<Option<Log>, web3::Error> {
+        let tx_hash = match transfer.eth_tx_hash {
+            Some(tx_hash) => tx_hash,
+            None => return Ok(None),
+        };
+        let log_index = transfer
+            .id
+            .rsplit(':')
+            .next()
+            .and_then(|index| index.parse::<u64>().ok());
+
+        let eth = web3::Web3::new(self.eth_transport.clone()).eth();
+        let receipt = match eth.transaction_receipt(tx_hash).await? {
+            Some(receipt) => receipt,
+            None => return Ok(None),
+        };
+        Ok(receipt.logs.into_iter().find(|log| {
+            log.address == self.eth_voip_bridge_address
+                && log.log_index.map(|index| index.as_u64()) == log_index
+        }))
+    }
+
+    /// The destination of a transfer already validated once, failing it when unreadable.
+    fn destination(&self, transfer: &Transfer) -> Option<Pubkey> {
+        match Pubkey::from_str(&transfer.solana_address) {
+            Ok(address) => Some(address),
+            Err(err) => {
+                self.fail(&transfer.id, format!("invalid destination: {err}"));
+                None
+            }
+        }
+    }
+
+    fn report_resumed(&self, transfer: &Transfer, eth_tx_hash: &str) {
+        println!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Resumed Stuck Transfer 💥
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:     {}
+                        Sol Address:  {}
+                        Eth Tx Hash:  {eth_tx_hash}
+                        Status:       Success✅
+            ",
+            transfer.id, transfer.solana_address
+        );
+    }
+
+    fn report_unresumed(&self, id: &str, err: &str) {
+        println!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Failed to Resume Stuck Transfer
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Transfer:    {id}
+                        Status:      Paused⏸️
+                        Error:       {err}
+            "
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claims_a_transfer_once_until_released() {
+        let in_flight = InFlight::default();
+        let claim = in_flight.claim("a");
+        assert!(claim.is_some());
+        assert!(in_flight.claim("a").is_none());
+        assert!(in_flight.claim("b").is_some());
+
+        drop(claim);
+        assert!(in_flight.claim("a").is_some());
+    }
+}
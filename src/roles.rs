@@ -0,0 +1,145 @@
+use crate::endpoints::FailoverHttp;
+use crate::gate::Gate;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_client::solana_sdk::signature::Keypair;
+use anchor_client::Program;
+use std::sync::Arc;
+use std::time::Duration;
+use web3::contract::{Contract, Options};
+use web3::types::H160;
+
+pub const ETH_RELAYER_REASON: &str = "ethereum key is not the bridge relayer";
+pub const SOL_ADMIN_REASON: &str = "solana key is not the migration admin";
+
+/// Relayer keys compared against the roles recorded on both chains.
+pub struct RoleCheck {
+    pub eth_relayer: H160,
+    pub bridge_relayer: H160,
+    pub bridge_owner: H160,
+    pub sol_admin: Pubkey,
+    pub state_admin: Pubkey,
+}
+
+impl RoleCheck {
+    /// Whether the Ethereum key may call `burnTokens` and `unlockTokens`.
+    pub fn eth_ok(&self) -> bool {
+        self.eth_relayer == self.bridge_relayer
+    }
+
+    /// Whether the Solana key may call `migrate`.
+    pub fn sol_ok(&self) -> bool {
+        self.sol_admin == self.state_admin
+    }
+}
+
+/// Reads `relayer()` and `owner()` from the bridge and the admin from the migration `state` account.
+pub async fn check_roles(
+    contract: &Contract<FailoverHttp>,
+    program: &Program<Arc<Keypair>>,
+    state_pda: &Pubkey,
+    eth_relayer: H160,
+    sol_admin: Pubkey,
+) -> Result<RoleCheck, Box<dyn std::error::Error>> {
+    let bridge_relayer: H160 = contract
+        .query("relayer", (), None, Options::default(), None)
+        .await?;
+    let bridge_owner: H160 = contract
+        .query("owner", (), None, Options::default(), None)
+        .await?;
+
+    let state: voip_migration::State = program.account(*state_pda).await?;
+
+    Ok(RoleCheck {
+        eth_relayer,
+        bridge_relayer,
+        bridge_owner,
+        sol_admin,
+        state_admin: state.admin,
+    })
+}
+
+/// Halts processing for each role the relayer keys no longer hold, reopening once restored.
+pub fn apply_roles(gate: &Gate, roles: &RoleCheck) {
+    if !roles.eth_ok() && gate.close(ETH_RELAYER_REASON) {
+        print_role_lost(
+            ETH_RELAYER_REASON,
+            &format!("{:?}", roles.eth_relayer),
+            &format!("{:?}", roles.bridge_relayer),
+        );
+    } else if roles.eth_ok() && gate.open(ETH_RELAYER_REASON) {
+        print_role_restored(ETH_RELAYER_REASON);
+    }
+
+    if !roles.sol_ok() && gate.close(SOL_ADMIN_REASON) {
+        print_role_lost(
+            SOL_ADMIN_REASON,
+            &roles.sol_admin.to_string(),
+            &roles.state_admin.to_string(),
+        );
+    } else if roles.sol_ok() && gate.open(SOL_ADMIN_REASON) {
+        print_role_restored(SOL_ADMIN_REASON);
+    }
+}
+
+/// Checks relayer roles and applies the result to `gate`.
+pub async fn recheck_roles(
+    gate: &Gate,
+    contract: &Contract<FailoverHttp>,
+    program: &Program<Arc<Keypair>>,
+    state_pda: &Pubkey,
+    eth_relayer: H160,
+    sol_admin: Pubkey,
+) {
+    match check_roles(contract, program, state_pda, eth_relayer, sol_admin).await {
+        Ok(roles) => apply_roles(gate, &roles),
+        Err(err) => println!(
+            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Failed to Check Relayer Roles
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Failed❌
+                            Error:       {err}
+                "
+        ),
+    }
+}
+
+/// Re-checks relayer roles every `interval`.
+pub async fn monitor_roles(
+    gate: &Gate,
+    contract: &Contract<FailoverHttp>,
+    program: &Program<Arc<Keypair>>,
+    state_pda: &Pubkey,
+    eth_relayer: H160,
+    sol_admin: Pubkey,
+    interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        recheck_roles(gate, contract, program, state_pda, eth_relayer, sol_admin).await;
+    }
+}
+
+fn print_role_lost(reason: &str, configured: &str, on_chain: &str) {
+    println!(
+        "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Relayer Halted: {reason}
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Configured:  {configured}
+                            On Chain:    {on_chain}
+                            Status:      Halted🛑
+                "
+    );
+}
+
+fn print_role_restored(reason: &str) {
+    println!(
+        "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Relayer Resumed: {reason} cleared
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Resumed▶️
+                "
+    );
+}
@@ -0,0 +1,287 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use web3::types::H160;
+
+/// What happens to a transfer that fails screening.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreeningAction {
+    /// Hold for manual approval.
+    #[default]
+    Hold,
+    /// Refund through `unlockTokens`.
+    Refund,
+}
+
+/// Screening file contents. Non-empty allowlists admit only the listed addresses.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ScreeningFile {
+    eth_deny: Vec<H160>,
+    eth_allow: Vec<H160>,
+    sol_deny: Vec<String>,
+    sol_allow: Vec<String>,
+    action: ScreeningAction,
+}
+
+#[derive(Default)]
+struct Lists {
+    eth_deny: HashSet<H160>,
+    eth_allow: HashSet<H160>,
+    sol_deny: HashSet<Pubkey>,
+    sol_allow: HashSet<Pubkey>,
+    action: ScreeningAction,
+}
+
+impl TryFrom<ScreeningFile> for Lists {
+    type Error = String;
+
+    fn try_from(file: ScreeningFile) -> Result<Self, String> {
+        let parse = |addresses: Vec<String>| {
+            addresses
+                .iter()
+                .map(|address| {
+                    Pubkey::from_str(address.trim())
+                        .map_err(|err| format!("invalid Solana address {address}: {err}"))
+                })
+                .collect::<Result<HashSet<_>, _>>()
+        };
+
+        Ok(Self {
+            eth_deny: file.eth_deny.into_iter().collect(),
+            eth_allow: file.eth_allow.into_iter().collect(),
+            sol_deny: parse(file.sol_deny)?,
+            sol_allow: parse(file.sol_allow)?,
+            action: file.action,
+        })
+    }
+}
+
+/// Deny and allow lists for Ethereum senders and Solana destinations, reloaded when the file changes.
+pub struct Screening {
+    path: Option<PathBuf>,
+    state: Mutex<(Option<SystemTime>, Lists)>,
+}
+
+impl Screening {
+    /// Loads the screening file at `path`; screening passes everything when `None`.
+    pub fn load(path: Option<String>) -> Self {
+        let screening = Self {
+            path: path.map(PathBuf::from),
+            state: Mutex::new((None, Lists::default())),
+        };
+
+        if let Err(err) = screening.reload() {
+            panic!(
+                "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                Failed to Load Screening File
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+                    Error:       {err}
+        "
+            );
+        }
+        screening
+    }
+
+    /// Returns the action and reason when `eth_user` or `solana_address` fails screening.
+    pub fn screen(
+        &self,
+        eth_user: H160,
+        solana_address: &str,
+    ) -> Option<(ScreeningAction, String)> {
+        if let Err(err) = self.reload() {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Failed to Reload Screening File
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Failed❌
+                            Error:       {err}
+                            Using:       previous lists
+                "
+            );
+        }
+
+        let state = self.state.lock().unwrap();
+        let lists = &state.1;
+        let destination = Pubkey::from_str(solana_address).ok();
+
+        let reason = if lists.eth_deny.contains(&eth_user) {
+            format!("ethereum sender {eth_user:?} is denylisted")
+        } else if !lists.eth_allow.is_empty() && !lists.eth_allow.contains(&eth_user) {
+            format!("ethereum sender {eth_user:?} is not allowlisted")
+        } else if destination.is_some_and(|destination| lists.sol_deny.contains(&destination)) {
+            format!("solana destination {solana_address} is denylisted")
+        } else if !lists.sol_allow.is_empty()
+            && !destination.is_some_and(|destination| lists.sol_allow.contains(&destination))
+        {
+            format!("solana destination {solana_address} is not allowlisted")
+        } else {
+            return None;
+        };
+
+        Some((lists.action, reason))
+    }
+
+    /// Re-reads the screening file when its modification time changed.
+    fn reload(&self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        if self.state.lock().unwrap().0 == Some(modified) {
+            return Ok(());
+        }
+
+        let bytes = fs::read(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let file: ScreeningFile =
+            serde_json::from_slice(&bytes).map_err(|err| format!("{}: {err}", path.display()))?;
+        let lists = Lists::try_from(file)?;
+
+        println!(
+            "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Loaded Screening Lists
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            ETH Deny:    {}
+                            ETH Allow:   {}
+                            SOL Deny:    {}
+                            SOL Allow:   {}
+                            Action:      {:?}
+                            Status:      Success✅
+                ",
+            lists.eth_deny.len(),
+            lists.eth_allow.len(),
+            lists.sol_deny.len(),
+            lists.sol_allow.len(),
+            lists.action
+        );
+        // a broken file keeps failing until it is fixed, rather than being skipped once reported
+        *self.state.lock().unwrap() = (Some(modified), lists);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const SOL_LISTED: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+    const SOL_OTHER: &str = "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T";
+
+    /// Writes `contents` to the screening file at `path`, stamped `age` seconds after the epoch.
+    fn write(path: &PathBuf, contents: &str, age: u64) {
+        fs::write(path, contents).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(age))
+            .unwrap();
+    }
+
+    fn screening_file(name: &str, contents: &str) -> (PathBuf, Screening) {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("screening.json");
+        write(&path, contents, 1);
+        let screening = Screening::load(Some(path.display().to_string()));
+        (path, screening)
+    }
+
+    #[test]
+    fn passes_everything_without_a_file() {
+        let screening = Screening::load(None);
+        assert!(screening.screen(H160::repeat_byte(1), SOL_OTHER).is_none());
+    }
+
+    #[test]
+    fn matches_deny_lists_on_each_chain_with_the_configured_action() {
+        let denied = H160::repeat_byte(1);
+        let (path, screening) = screening_file(
+            "screening-deny",
+            &format!(
+                r#"{{"eth_deny": ["{denied:?}"], "sol_deny": ["{SOL_LISTED}"], "action": "refund"}}"#
+            ),
+        );
+
+        let (action, reason) = screening.screen(denied, SOL_OTHER).unwrap();
+        assert_eq!(action, ScreeningAction::Refund);
+        assert!(reason.contains("is denylisted"), "{reason}");
+
+        let (_, reason) = screening.screen(H160::repeat_byte(2), SOL_LISTED).unwrap();
+        assert_eq!(
+            reason,
+            format!("solana destination {SOL_LISTED} is denylisted")
+        );
+
+        assert!(screening.screen(H160::repeat_byte(2), SOL_OTHER).is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn admits_only_allow_listed_addresses_and_holds_by_default() {
+        let allowed = H160::repeat_byte(1);
+        let (path, screening) = screening_file(
+            "screening-allow",
+            &format!(r#"{{"eth_allow": ["{allowed:?}"], "sol_allow": ["{SOL_LISTED}"]}}"#),
+        );
+
+        assert!(screening.screen(allowed, SOL_LISTED).is_none());
+
+        let (action, reason) = screening.screen(H160::repeat_byte(2), SOL_LISTED).unwrap();
+        assert_eq!(action, ScreeningAction::Hold);
+        assert!(reason.contains("is not allowlisted"), "{reason}");
+
+        let (_, reason) = screening.screen(allowed, SOL_OTHER).unwrap();
+        assert_eq!(
+            reason,
+            format!("solana destination {SOL_OTHER} is not allowlisted")
+        );
+
+        // an unparseable destination is never on the allowlist
+        assert!(screening.screen(allowed, "not-a-key").is_some());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reloads_when_the_file_changes_and_keeps_the_last_good_lists() {
+        let user = H160::repeat_byte(1);
+        let (path, screening) = screening_file("screening-reload", "{}");
+        assert!(screening.screen(user, SOL_OTHER).is_none());
+
+        write(&path, &format!(r#"{{"eth_deny": ["{user:?}"]}}"#), 2);
+        assert!(screening.screen(user, SOL_OTHER).is_some());
+
+        // a broken edit keeps the previous lists until it is fixed
+        write(&path, r#"{"eth_deny": ["#, 3);
+        assert!(screening.screen(user, SOL_OTHER).is_some());
+
+        write(&path, "{}", 4);
+        assert!(screening.screen(user, SOL_OTHER).is_none());
+
+        // the same modification time is not read again
+        write(&path, &format!(r#"{{"eth_deny": ["{user:?}"]}}"#), 4);
+        assert!(screening.screen(user, SOL_OTHER).is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to Load Screening File")]
+    fn refuses_an_invalid_file_on_startup() {
+        screening_file("screening-invalid", r#"{"sol_deny": ["not-a-key"]}"#);
+    }
+}
@@ -0,0 +1,494 @@
+use crate::ledger::{Decision, Ledger, Transfer, TransferStatus};
+use crate::metrics::Metrics;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use web3::types::{H160, H256};
+
+/// An operator's bearer token, parsed from `<actor>:<token>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminToken {
+    /// Recorded in the audit trail for decisions made with this token.
+    pub actor: String,
+    pub token: String,
+}
+
+impl FromStr for AdminToken {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        match value.split_once(':') {
+            Some((actor, token)) if !actor.trim().is_empty() && !token.trim().is_empty() => {
+                Ok(Self {
+                    actor: actor.trim().to_string(),
+                    token: token.trim().to_string(),
+                })
+            }
+            _ => Err(format!("expected <actor>:<token>, got {value}")),
+        }
+    }
+}
+
+/// Shared state behind the relayer's HTTP endpoints.
+pub struct ServerState {
+    pub metrics: Arc<Metrics>,
+    pub ledger: Arc<Ledger>,
+    /// Bearer tokens for the held queue, which is disabled when empty.
+    pub admin_tokens: Vec<AdminToken>,
+}
+
+/// Body of the held queue decision endpoints.
+#[derive(Deserialize)]
+struct DecisionRequest {
+    note: Option<String>,
+}
+
+/// Serves relayer metrics, transfer lookups and the held queue on `addr`.
+pub async fn serve(addr: SocketAddr, state: Arc<ServerState>) {
+    let server = match Server::try_bind(&addr) {
+        Ok(server) => server,
+        Err(err) => panic!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Failed to Bind HTTP Server
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Address:     {addr}
+                        Status:      Failed❌
+                        Error:       {err}
+            "
+        ),
+    };
+
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(route(request, &state).await) }
+            }))
+        }
+    });
+
+    if let Err(err) = server.serve(make_service).await {
+        println!(
+            "
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      HTTP Server Stopped
+                ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Status:      Failed❌
+                        Error:       {err}
+            "
+        );
+    }
+}
+
+async fn route(request: Request<Body>, state: &ServerState) -> Response<Body> {
+    let path = request.uri().path().to_string();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (request.method(), segments.as_slice()) {
+        (&Method::GET, ["metrics"]) => Response::builder()
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Body::from(state.metrics.render()))
+            .unwrap(),
+        (&Method::GET, ["transfers", key, value]) => lookup(state, key, value),
+        (_, ["held" | "supply", ..]) => match authorized(&request, state) {
+            Some(actor) => admin_route(request, state, &segments, actor).await,
+            None => json_response(
+                StatusCode::UNAUTHORIZED,
+                json!({ "error": "missing or invalid admin token" }),
+            ),
+        },
+        _ => not_found(),
+    }
+}
+
+/// Held queue and supply endpoints, on behalf of the operator `actor` the token belongs to.
+async fn admin_route(
+    request: Request<Body>,
+    state: &ServerState,
+    segments: &[&str],
+    actor: &str,
+) -> Response<Body> {
+    match (request.method(), segments) {
+        (&Method::GET, ["held"]) => json_response(
+            StatusCode::OK,
+            json!(state.ledger.with_status(&[TransferStatus::Held])),
+        ),
+        (&Method::POST, ["held", id, action]) => {
+            let decision = match *action {
+                "approve" => Decision::Approve,
+                "reject" => Decision::Reject,
+                "annotate" => Decision::Annotate,
+                _ => return not_found(),
+            };
+            let id = id.to_string();
+            decide(request, state, &id, decision, actor).await
+        }
+        (&Method::POST, ["supply", "rebaseline"]) => {
+            state.ledger.reset_supply();
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                        Supply Baseline Reset
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Actor:       {actor}
+                            Status:      Success✅
+                "
+            );
+            json_response(
+                StatusCode::OK,
+                json!({ "supply": null, "note": "a new baseline is taken on the next supply check" }),
+            )
+        }
+        _ => not_found(),
+    }
+}
+
+async fn decide(
+    request: Request<Body>,
+    state: &ServerState,
+    id: &str,
+    decision: Decision,
+    actor: &str,
+) -> Response<Body> {
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(err) => {
+            return json_response(StatusCode::BAD_REQUEST, json!({ "error": err.to_string() }))
+        }
+    };
+    // approvals and rejections need no body
+    let note = match serde_json::from_slice::<DecisionRequest>(&body) {
+        Ok(decision_request) => decision_request.note,
+        Err(_) if body.is_empty() => None,
+        Err(err) => {
+            return json_response(StatusCode::BAD_REQUEST, json!({ "error": err.to_string() }))
+        }
+    };
+
+    match state.ledger.decide(id, decision, actor, note) {
+        Ok(transfer) => {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                          Held Transfer Decision
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Transfer:    {id}
+                            Decision:    {decision:?}
+                            Actor:       {actor}
+                            Status:      Success✅
+                "
+            );
+            json_response(StatusCode::OK, json!(transfer))
+        }
+        Err(err) => json_response(StatusCode::CONFLICT, json!({ "error": err })),
+    }
+}
+
+/// Transfers by Ethereum tx hash, Ethereum user or Solana destination.
+fn lookup(state: &ServerState, key: &str, value: &str) -> Response<Body> {
+    let transfers = match key {
+        "eth-tx" => match H256::from_str(value) {
+            Ok(hash) => state
+                .ledger
+                .find(|transfer| transfer.eth_tx_hash == Some(hash)),
+            Err(_) => return bad_request("invalid ethereum transaction hash"),
+        },
+        "eth-user" => match H160::from_str(value) {
+            Ok(user) => state.ledger.find(|transfer| transfer.eth_user == user),
+            Err(_) => return bad_request("invalid ethereum address"),
+        },
+        "sol-address" => match Pubkey::from_str(value) {
+            Ok(address) => {
+                let address = address.to_string();
+                state
+                    .ledger
+                    .find(|transfer| transfer.solana_address == address)
+            }
+            Err(_) => return bad_request("invalid solana address"),
+        },
+        _ => return not_found(),
+    };
+
+    let transfers: Vec<_> = transfers.iter().map(transfer_view).collect();
+    json_response(StatusCode::OK, json!({ "transfers": transfers }))
+}
+
+/// The parts of `transfer` shown to lookups, leaving out the held queue audit trail.
+fn transfer_view(transfer: &Transfer) -> serde_json::Value {
+    json!({
+        "id": transfer.id,
+        "eth_tx_hash": transfer.eth_tx_hash,
+        "eth_user": transfer.eth_user,
+        "solana_address": transfer.solana_address,
+        "eth_amount": transfer.eth_amount,
+        "amount": transfer.amount,
+        "status": transfer.status,
+        "sol_signature": transfer.sol_signature,
+        "eth_burn_tx_hash": transfer.eth_burn_tx_hash,
+        "eth_refund_tx_hash": transfer.eth_refund_tx_hash,
+        "error": transfer.error,
+        "created_at": transfer.created_at,
+        "updated_at": transfer.updated_at,
+    })
+}
+
+/// The operator whose token authorizes `request`, if any.
+fn authorized<'s>(request: &Request<Body>, state: &'s ServerState) -> Option<&'s str> {
+    let provided = request
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))?;
+
+    state
+        .admin_tokens
+        .iter()
+        .find(|admin| token_matches(provided, &admin.token))
+        .map(|admin| admin.actor.as_str())
+}
+
+// compare every byte so response timing does not leak the token
+fn token_matches(provided: &str, token: &str) -> bool {
+    provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn bad_request(error: &str) -> Response<Body> {
+    json_response(StatusCode::BAD_REQUEST, json!({ "error": error }))
+}
+
+fn not_found() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::tests::lock_log;
+    use web3::types::U256;
+
+    const WALLET: &str = "So11111111111111111111111111111111111111112";
+
+    fn state(admin_tokens: &[&str]) -> ServerState {
+        ServerState {
+            metrics: Arc::new(Metrics::default()),
+            ledger: Arc::new(Ledger::open("/nonexistent/ledger.json", true).unwrap()),
+            admin_tokens: admin_tokens.iter().map(|t| t.parse().unwrap()).collect(),
+        }
+    }
+
+    fn record(state: &ServerState, tx: u8, status: TransferStatus) -> Transfer {
+        let user = H160::repeat_byte(0x42);
+        let mut log = lock_log(U256::from(5), user, WALLET);
+        log.transaction_hash = Some(H256::repeat_byte(tx));
+        let (transfer, _) = state.ledger.record(Transfer {
+            status,
+            ..Transfer::new(&log, user, WALLET, U256::from(5))
+        });
+        transfer
+    }
+
+    fn request(method: Method, path: &str, token: Option<&str>, body: &str) -> Request<Body> {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {token}"));
+        }
+        request.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn call(state: &ServerState, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = route(request, state).await;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[test]
+    fn admin_tokens_parse_as_actor_and_token() {
+        assert_eq!(
+            " alice : s3cret ".parse(),
+            Ok(AdminToken {
+                actor: "alice".to_string(),
+                token: "s3cret".to_string(),
+            })
+        );
+        assert!("s3cret".parse::<AdminToken>().is_err());
+        assert!(":s3cret".parse::<AdminToken>().is_err());
+        assert!("alice:".parse::<AdminToken>().is_err());
+    }
+
+    #[tokio::test]
+    async fn transfers_are_looked_up_by_each_key() {
+        let state = state(&[]);
+        let transfer = record(&state, 0x11, TransferStatus::Completed);
+        record(&state, 0x12, TransferStatus::Pending);
+
+        let tx = format!("{:?}", H256::repeat_byte(0x11));
+        let user = format!("{:?}", H160::repeat_byte(0x42));
+        for (path, found) in [
+            (format!("/transfers/eth-tx/{tx}"), 1),
+            (format!("/transfers/eth-user/{user}"), 2),
+            (format!("/transfers/sol-address/{WALLET}"), 2),
+            (format!("/transfers/eth-user/{:?}", H160::zero()), 0),
+        ] {
+            let (status, body) = call(&state, request(Method::GET, &path, None, "")).await;
+            assert_eq!(status, StatusCode::OK, "{path}");
+            assert_eq!(body["transfers"].as_array().unwrap().len(), found, "{path}");
+        }
+
+        let (_, body) = call(
+            &state,
+            request(Method::GET, &format!("/transfers/eth-tx/{tx}"), None, ""),
+        )
+        .await;
+        assert_eq!(body["transfers"][0]["id"], json!(transfer.id));
+        assert!(body["transfers"][0].get("audit").is_none());
+    }
+
+    #[tokio::test]
+    async fn lookups_reject_invalid_values_and_unknown_keys() {
+        let state = state(&[]);
+        for path in [
+            "/transfers/eth-tx/0x12",
+            "/transfers/eth-user/nope",
+            "/transfers/sol-address/0x42",
+        ] {
+            let (status, _) = call(&state, request(Method::GET, path, None, "")).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{path}");
+        }
+
+        let (status, _) = call(
+            &state,
+            request(Method::GET, "/transfers/eth-block/1", None, ""),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn admin_routes_need_a_configured_token() {
+        let disabled = state(&[]);
+        let (status, _) = call(&disabled, request(Method::GET, "/held", Some(""), "")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let state = state(&["alice:s3cret"]);
+        for token in [None, Some("s3cre"), Some("s3cret!"), Some("alice:s3cret")] {
+            for (method, path) in [
+                (Method::GET, "/held"),
+                (Method::POST, "/held/x/approve"),
+                (Method::POST, "/supply/rebaseline"),
+            ] {
+                let (status, _) = call(&state, request(method, path, token, "")).await;
+                assert_eq!(status, StatusCode::UNAUTHORIZED, "{path} with {token:?}");
+            }
+        }
+
+        let (status, body) = call(&state, request(Method::GET, "/held", Some("s3cret"), "")).await;
+        assert_eq!((status, body), (StatusCode::OK, json!([])));
+    }
+
+    #[tokio::test]
+    async fn held_lists_only_held_transfers() {
+        let state = state(&["alice:s3cret"]);
+        let held = record(&state, 0x11, TransferStatus::Held);
+        record(&state, 0x12, TransferStatus::Pending);
+
+        let (status, body) = call(&state, request(Method::GET, "/held", Some("s3cret"), "")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["id"], json!(held.id));
+    }
+
+    #[tokio::test]
+    async fn decisions_are_recorded_for_the_token_holder() {
+        let state = state(&["alice:s3cret", "bob:hunter2"]);
+        let approved = record(&state, 0x11, TransferStatus::Held);
+        let rejected = record(&state, 0x12, TransferStatus::Held);
+
+        // an actor in the body is ignored
+        let path = format!("/held/{}/approve", approved.id);
+        let body = r#"{"actor": "mallory", "note": "kyc ok"}"#;
+        let (status, body) = call(&state, request(Method::POST, &path, Some("s3cret"), body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], json!(TransferStatus::Approved));
+        assert_eq!(body["audit"][0]["actor"], json!("alice"));
+        assert_eq!(body["audit"][0]["note"], json!("kyc ok"));
+
+        let path = format!("/held/{}/reject", rejected.id);
+        let (status, body) = call(&state, request(Method::POST, &path, Some("hunter2"), "")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], json!(TransferStatus::Rejected));
+        assert_eq!(body["audit"][0]["actor"], json!("bob"));
+        assert_eq!(body["audit"][0]["note"], json!(null));
+
+        let path = format!("/held/{}/annotate", rejected.id);
+        let body = r#"{"note": "refund on monday"}"#;
+        let (status, body) = call(&state, request(Method::POST, &path, Some("s3cret"), body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], json!(TransferStatus::Rejected));
+        assert_eq!(body["audit"][1]["actor"], json!("alice"));
+        assert_eq!(body["audit"][1]["action"], json!(Decision::Annotate));
+    }
+
+    #[tokio::test]
+    async fn invalid_decisions_conflict() {
+        let state = state(&["alice:s3cret"]);
+        let held = record(&state, 0x11, TransferStatus::Held);
+        let pending = record(&state, 0x12, TransferStatus::Pending);
+
+        for (path, body, expected) in [
+            (
+                format!("/held/{}/annotate", held.id),
+                "",
+                StatusCode::CONFLICT,
+            ),
+            (
+                format!("/held/{}/approve", pending.id),
+                "",
+                StatusCode::CONFLICT,
+            ),
+            ("/held/unknown/reject".to_string(), "", StatusCode::CONFLICT),
+            (
+                format!("/held/{}/approve", held.id),
+                "{",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                format!("/held/{}/escalate", held.id),
+                "",
+                StatusCode::NOT_FOUND,
+            ),
+        ] {
+            let (status, _) =
+                call(&state, request(Method::POST, &path, Some("s3cret"), body)).await;
+            assert_eq!(status, expected, "{path}");
+        }
+
+        let transfer = state.ledger.get(&held.id).unwrap();
+        assert_eq!(transfer.status, TransferStatus::Held);
+        assert!(transfer.audit.is_empty());
+    }
+}
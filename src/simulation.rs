@@ -0,0 +1,255 @@
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_client::rpc_response::RpcSimulateTransactionResult;
+use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
+use anchor_client::solana_sdk::instruction::{Instruction, InstructionError};
+use anchor_client::solana_sdk::signature::{Keypair, Signer};
+use anchor_client::solana_sdk::transaction::{Transaction, TransactionError};
+use std::fmt;
+
+// most compute units a single transaction may request
+const MAX_COMPUTE_UNITS: u32 = 1_400_000;
+
+// anchor numbers program-defined errors from here up
+const PROGRAM_ERROR_OFFSET: u32 = 6000;
+
+// anchor's own instruction, constraint and account errors start here
+const ANCHOR_ERROR_START: u32 = 100;
+
+/// Compute budget for migrate transactions.
+pub struct ComputeBudget {
+    /// Headroom added to the simulated compute units.
+    pub margin_percent: u32,
+    /// Priority fee in micro-lamports per compute unit, none when unset.
+    pub unit_price: Option<u64>,
+}
+
+/// An error raised by the migration program itself, as logged by anchor.
+#[derive(Debug, Clone)]
+pub struct ProgramError {
+    pub code: u32,
+    pub name: String,
+    pub message: String,
+}
+
+/// What happens to a transfer the migration program rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    /// The transfer can never migrate, so the lock is returned to the user.
+    Refund,
+    /// Held for an operator to approve or reject.
+    Hold,
+}
+
+impl ProgramError {
+    /// Refunds migration program errors listed by name or number in `refund_errors`, holding everything else.
+    pub fn disposition(&self, refund_errors: &[String]) -> Disposition {
+        // anchor's account and constraint checks failing points at the relayer's accounts, not the transfer
+        if self.code < PROGRAM_ERROR_OFFSET {
+            return Disposition::Hold;
+        }
+
+        let code = self.code.to_string();
+        if refund_errors
+            .iter()
+            .any(|refund| *refund == self.name || *refund == code)
+        {
+            Disposition::Refund
+        } else {
+            Disposition::Hold
+        }
+    }
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.name, self.code, self.message)
+    }
+}
+
+/// The anchor error behind a failed simulation, when it was raised by the program or its account checks rather than the runtime.
+pub fn program_error(result: &RpcSimulateTransactionResult) -> Option<ProgramError> {
+    let logged = result
+        .logs
+        .iter()
+        .flatten()
+        .find_map(|log| parse_anchor_log(log));
+    if let Some(err) = logged.filter(|err| err.code >= ANCHOR_ERROR_START) {
+        return Some(err);
+    }
+
+    // fall back to the raw custom code when the program did not log it
+    match &result.err {
+        Some(TransactionError::InstructionError(_, InstructionError::Custom(code)))
+            if *code >= PROGRAM_ERROR_OFFSET =>
+        {
+            Some(ProgramError {
+                code: *code,
+                name: "Custom".to_string(),
+                message: "program error".to_string(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Parses "... Error Code: <name>. Error Number: <code>. Error Message: <message>." from an anchor log line.
+fn parse_anchor_log(log: &str) -> Option<ProgramError> {
+    let (_, rest) = log.split_once("Error Code: ")?;
+    let (name, rest) = rest.split_once(". Error Number: ")?;
+    let (code, message) = rest.split_once(". Error Message: ")?;
+
+    Some(ProgramError {
+        code: code.trim().parse().ok()?,
+        name: name.trim().to_string(),
+        message: message.trim().trim_end_matches('.').to_string(),
+    })
+}
+
+/// Compute budget instructions for a transaction that used `units_consumed` in simulation.
+pub fn compute_budget_instructions(
+    budget: &ComputeBudget,
+    units_consumed: Option<u64>,
+) -> Vec<Instruction> {
+    let limit = match units_consumed {
+        Some(units) => units
+            .saturating_mul(100 + u64::from(budget.margin_percent))
+            .div_ceil(100)
+            .min(u64::from(MAX_COMPUTE_UNITS)) as u32,
+        None => MAX_COMPUTE_UNITS,
+    };
+
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(limit)];
+    if let Some(price) = budget.unit_price {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+    }
+    instructions
+}
+
+/// Signs `instructions` as the admin with a fresh blockhash.
+pub async fn sign(
+    connection: &RpcClient,
+    sol_admin_keypair: &Keypair,
+    instructions: &[Instruction],
+) -> Result<Transaction, Box<dyn std::error::Error>> {
+    let latest_blockhash = connection.get_latest_blockhash().await?;
+    let mut transaction =
+        Transaction::new_with_payer(instructions, Some(&sol_admin_keypair.pubkey()));
+    transaction.sign(&[sol_admin_keypair], latest_blockhash);
+    Ok(transaction)
+}
+
+/// Runs `instructions` through `simulateTransaction` with the largest compute budget, without broadcasting them.
+pub async fn simulate(
+    connection: &RpcClient,
+    sol_admin_keypair: &Keypair,
+    budget: &ComputeBudget,
+    instructions: &[Instruction],
+) -> Result<RpcSimulateTransactionResult, Box<dyn std::error::Error>> {
+    let mut budgeted = compute_budget_instructions(budget, None);
+    budgeted.extend_from_slice(instructions);
+    let transaction = sign(connection, sol_admin_keypair, &budgeted).await?;
+
+    Ok(connection.simulate_transaction(&transaction).await?.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulation(err: Option<TransactionError>, logs: &[&str]) -> RpcSimulateTransactionResult {
+        RpcSimulateTransactionResult {
+            err,
+            logs: Some(logs.iter().map(|log| log.to_string()).collect()),
+            accounts: None,
+            units_consumed: None,
+            return_data: None,
+            inner_instructions: None,
+        }
+    }
+
+    #[test]
+    fn parses_anchor_error_logs() {
+        let err = parse_anchor_log(
+            "Program log: AnchorError thrown in programs/voip-migration/src/lib.rs:42. Error Code: MigrationPaused. Error Number: 6003. Error Message: Migration is paused.",
+        )
+        .unwrap();
+        assert_eq!(
+            (err.code, err.name.as_str(), err.message.as_str()),
+            (6003, "MigrationPaused", "Migration is paused")
+        );
+
+        let err = parse_anchor_log(
+            "Program log: AnchorError caused by account: state. Error Code: ConstraintSeeds. Error Number: 2006. Error Message: A seeds constraint was violated.",
+        )
+        .unwrap();
+        assert_eq!((err.code, err.name.as_str()), (2006, "ConstraintSeeds"));
+    }
+
+    #[test]
+    fn ignores_other_log_lines() {
+        assert!(parse_anchor_log("Program log: Instruction: Migrate").is_none());
+        assert!(parse_anchor_log("Program log: Error: insufficient funds").is_none());
+        assert!(parse_anchor_log(
+            "Program log: Error Code: Broken. Error Number: x. Error Message: bad."
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn finds_the_program_error_of_a_failed_simulation() {
+        let logged = simulation(
+            Some(TransactionError::InstructionError(1, InstructionError::Custom(6001))),
+            &[
+                "Program log: Instruction: Migrate",
+                "Program log: AnchorError occurred. Error Code: InvalidAmount. Error Number: 6001. Error Message: Invalid amount.",
+            ],
+        );
+        assert_eq!(program_error(&logged).unwrap().name, "InvalidAmount");
+
+        let unlogged = simulation(
+            Some(TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(6002),
+            )),
+            &[],
+        );
+        assert_eq!(program_error(&unlogged).unwrap().code, 6002);
+
+        // token program errors are custom codes below anchor's range
+        let token = simulation(
+            Some(TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(1),
+            )),
+            &["Program log: Error: insufficient funds"],
+        );
+        assert!(program_error(&token).is_none());
+    }
+
+    #[test]
+    fn refunds_only_listed_program_errors() {
+        let error = |code, name: &str| ProgramError {
+            code,
+            name: name.to_string(),
+            message: String::new(),
+        };
+        let refund_errors = vec!["InvalidDestination".to_string(), "6005".to_string()];
+
+        assert_eq!(
+            error(6004, "InvalidDestination").disposition(&refund_errors),
+            Disposition::Refund
+        );
+        assert_eq!(
+            error(6005, "Custom").disposition(&refund_errors),
+            Disposition::Refund
+        );
+        assert_eq!(
+            error(6006, "MigrationPaused").disposition(&refund_errors),
+            Disposition::Hold
+        );
+        assert_eq!(
+            error(2006, "6005").disposition(&refund_errors),
+            Disposition::Hold
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use crate::config::Settings;
+use crate::endpoints::FailoverHttp;
+use crate::gate::Gate;
+use crate::held::send_admin_request;
+use crate::ledger::{Ledger, SupplyBaseline};
+use crate::metrics::Metrics;
+use crate::reconcile::{signatures_for_address, token_balance_change};
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_client::solana_sdk::signature::Signature;
+use reqwest::Method;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use web3::contract::{Contract, Options};
+use web3::types::{Address, U256};
+
+pub const SUPPLY_DRIFT_REASON: &str = "supply invariant drift";
+
+/// Cross-chain totals behind the supply invariant.
+pub struct SupplyTotals {
+    /// VOIP held by the bridge, in ERC20 base units.
+    pub bridge_locked: U256,
+    /// Recorded locks the bridge should still hold, in ERC20 base units.
+    pub ledger_outstanding: U256,
+    pub admin_ata_balance: u64,
+    /// Admin ATA outflow since the baseline, in SPL base units.
+    pub admin_outflow: u64,
+    /// Recorded migrations since the baseline, in SPL base units.
+    pub ledger_migrated: u64,
+    /// Bridge balance change not explained by recorded locks, burns and refunds.
+    pub lock_drift: U256,
+    /// Admin ATA outflow not explained by recorded migrations.
+    pub migration_drift: u64,
+}
+
+impl SupplyTotals {
+    /// Compares the balances read now with the ledger, as changes since `baseline`.
+    pub fn since(
+        baseline: &SupplyBaseline,
+        bridge_locked: U256,
+        ledger_outstanding: U256,
+        admin_ata_balance: u64,
+        ledger_migrated_total: u64,
+    ) -> Self {
+        let ledger_migrated = ledger_migrated_total.saturating_sub(baseline.ledger_migrated);
+
+        // arranged to stay unsigned
+        let lock_drift = abs_diff(
+            bridge_locked.saturating_add(baseline.ledger_outstanding),
+            ledger_outstanding.saturating_add(baseline.bridge_locked),
+        );
+        let migration_drift = baseline.admin_outflow.abs_diff(ledger_migrated);
+
+        Self {
+            bridge_locked,
+            ledger_outstanding,
+            admin_ata_balance,
+            admin_outflow: baseline.admin_outflow,
+            ledger_migrated,
+            lock_drift,
+            migration_drift,
+        }
+    }
+}
+
+/// How a check's drift stands against the tolerances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drift {
+    Within,
+    /// Over tolerance for the first check in a row.
+    Breached,
+    /// Over tolerance for a second consecutive check, which halts processing.
+    Sustained,
+}
+
+/// Assesses `totals`, given whether the previous check was over tolerance.
+pub fn assess(
+    totals: &SupplyTotals,
+    lock_tolerance: U256,
+    migration_tolerance: u64,
+    drifted_before: bool,
+) -> Drift {
+    // a single breach can be a lock or migration still being recorded
+    let over = totals.lock_drift > lock_tolerance || totals.migration_drift > migration_tolerance;
+    match (over, drifted_before) {
+        (false, _) => Drift::Within,
+        (true, false) => Drift::Breached,
+        (true, true) => Drift::Sustained,
+    }
+}
+
+/// Checks the bridge balance and admin ATA outflow against the ledger.
+pub struct SupplyMonitor<'a> {
+    pub eth_voip_token: Contract<FailoverHttp>,
+    pub bridge: Address,
+    pub admin_ata: Pubkey,
+    /// Owner and mint of the admin ATA, to pick its balance out of transaction metadata.
+    pub sol_admin: Pubkey,
+    pub sol_voip_token_mint: Pubkey,
+    pub ledger: &'a Ledger,
+    pub metrics: &'a Metrics,
+    /// Allowed lock drift, in ERC20 base units.
+    pub lock_tolerance: U256,
+    /// Allowed migration drift, in SPL base units.
+    pub migration_tolerance: u64,
+    /// Whether the previous check was over tolerance.
+    pub drifted: AtomicBool,
+}
+
+impl SupplyMonitor<'_> {
+    /// Reads both chains and folds them into the ledger's supply baseline.
+    pub async fn totals(
+        &self,
+        connection: &RpcClient,
+    ) -> Result<SupplyTotals, Box<dyn std::error::Error>> {
+        let bridge_locked: U256 = self
+            .eth_voip_token
+            .query("balanceOf", (self.bridge,), None, Options::default(), None)
+            .await?;
+        let admin_ata_balance = connection
+            .get_token_account_balance(&self.admin_ata)
+            .await?
+            .amount
+            .parse::<u64>()?;
+
+        // count what left the admin ATA transaction by transaction, so a top-up cannot hide it
+        let (outflow, last_signature) = match self
+            .ledger
+            .supply()
+            .and_then(|baseline| baseline.last_signature)
+        {
+            Some(until) => {
+                self.admin_outflow(connection, Signature::from_str(&until)?)
+                    .await?
+            }
+            None => (0, self.newest_signature(connection).await?),
+        };
+        let baseline =
+            match self
+                .ledger
+                .add_supply_outflow(outflow, admin_ata_balance, last_signature.clone())
+            {
+                Some(baseline) => baseline,
+                None => self
+                    .ledger
+                    .start_supply(bridge_locked, admin_ata_balance, last_signature),
+            };
+
+        Ok(SupplyTotals::since(
+            &baseline,
+            bridge_locked,
+            self.ledger.outstanding_locked(),
+            admin_ata_balance,
+            self.ledger.migrated_total(),
+        ))
+    }
+
+    /// VOIP sent out of the admin ATA by successful transactions after `until`, and the newest of them.
+    async fn admin_outflow(
+        &self,
+        connection: &RpcClient,
+        until: Signature,
+    ) -> Result<(u64, Option<String>), Box<dyn std::error::Error>> {
+        let signatures =
+            signatures_for_address(connection, &self.admin_ata, Some(until), None).await?;
+
+        let mut outflow: u64 = 0;
+        for status in &signatures {
+            if status.err.is_some() {
+                continue;
+            }
+            let signature = Signature::from_str(&status.signature)?;
+            let change = token_balance_change(
+                connection,
+                &signature,
+                &self.sol_voip_token_mint,
+                &self.sol_admin,
+            )
+            .await?;
+            if let Some(change) = change.filter(|change| *change < 0) {
+                outflow = outflow.saturating_add(u64::try_from(-change)?);
+            }
+        }
+
+        let newest = match signatures.first() {
+            Some(newest) => newest.signature.clone(),
+            None => until.to_string(),
+        };
+        Ok((outflow, Some(newest)))
+    }
+
+    /// The admin ATA's latest transaction, where outflow counting starts.
+    async fn newest_signature(
+        &self,
+        connection: &RpcClient,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let newest = connection
+            .get_signatures_for_address_with_config(
+                &self.admin_ata,
+                GetConfirmedSignaturesForAddress2Config {
+                    before: None,
+                    until: None,
+                    limit: Some(1),
+                    commitment: Some(connection.commitment()),
+                },
+            )
+            .await?;
+        Ok(newest.first().map(|newest| newest.signature.clone()))
+    }
+
+    /// Exports supply totals and halts `gate` once drift stays over tolerance for two checks.
+    pub async fn check(&self, gate: &Gate, connection: &RpcClient) {
+        let totals = match self.totals(connection).await {
+            Ok(totals) => totals,
+            Err(err) => {
+                println!(
+                    "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Failed to Check Supply Invariant
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Failed❌
+                            Error:       {err}
+                "
+                );
+                return;
+            }
+        };
+        self.export(gate, &totals);
+
+        let drifted_before = self.drifted.load(Ordering::Relaxed);
+        let drift = assess(
+            &totals,
+            self.lock_tolerance,
+            self.migration_tolerance,
+            drifted_before,
+        );
+        self.drifted
+            .store(drift != Drift::Within, Ordering::Relaxed);
+
+        if drift == Drift::Sustained && gate.close(SUPPLY_DRIFT_REASON) {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Relayer Halted: {SUPPLY_DRIFT_REASON}
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Bridge Locked:      {}
+                            Ledger Outstanding: {}
+                            Lock Drift:         {}
+                            Admin Outflow:      {}
+                            Ledger Migrated:    {}
+                            Migration Drift:    {}
+                            Status:             Halted🛑
+                ",
+                totals.bridge_locked,
+                totals.ledger_outstanding,
+                totals.lock_drift,
+                totals.admin_outflow,
+                totals.ledger_migrated,
+                totals.migration_drift
+            );
+        } else if drift == Drift::Within && gate.open(SUPPLY_DRIFT_REASON) {
+            println!(
+                "
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                      Relayer Resumed: {SUPPLY_DRIFT_REASON} cleared
+                    ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                            Status:      Resumed▶️
+                "
+            );
+        }
+    }
+
+    /// Re-checks the supply invariant every `interval`.
+    pub async fn monitor(&self, gate: &Gate, connection: &RpcClient, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            self.check(gate, connection).await;
+        }
+    }
+
+    fn export(&self, gate: &Gate, totals: &SupplyTotals) {
+        let metrics = self.metrics;
+        metrics.set(
+            "voip_bridge_locked",
+            "VOIP held by the Ethereum bridge, in ERC20 base units.",
+            to_f64(totals.bridge_locked),
+        );
+        metrics.set(
+            "voip_ledger_outstanding_locked",
+            "Recorded locks the bridge should still hold, in ERC20 base units.",
+            to_f64(totals.ledger_outstanding),
+        );
+        metrics.set(
+            "voip_admin_ata_balance",
+            "VOIP held by the Solana admin ATA, in SPL base units.",
+            totals.admin_ata_balance as f64,
+        );
+        metrics.set(
+            "voip_admin_ata_outflow",
+            "VOIP sent from the admin ATA since the supply baseline, in SPL base units.",
+            totals.admin_outflow as f64,
+        );
+        metrics.set(
+            "voip_ledger_migrated",
+            "Recorded migrations since the supply baseline, in SPL base units.",
+            totals.ledger_migrated as f64,
+        );
+        metrics.set(
+            "voip_supply_lock_drift",
+            "Bridge balance change not explained by recorded transfers, in ERC20 base units.",
+            to_f64(totals.lock_drift),
+        );
+        metrics.set(
+            "voip_supply_migration_drift",
+            "Admin ATA outflow not explained by recorded migrations, in SPL base units.",
+            totals.migration_drift as f64,
+        );
+        metrics.set(
+            "voip_relayer_halt_reasons",
+            "Number of reasons processing is currently halted for.",
+            gate.reasons().len() as f64,
+        );
+    }
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+fn to_f64(value: U256) -> f64 {
+    value.to_string().parse().unwrap_or(f64::MAX)
+}
+
+/// Runs `supply rebaseline` against the running relayer's HTTP server.
+pub async fn run_supply(args: &[String], settings: &Settings) {
+    match args {
+        [command] if command == "rebaseline" => {
+            send_admin_request(settings, Method::POST, "/supply/rebaseline", None).await
+        }
+        _ => panic!(
+            "
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+              Usage: voip-relayer-rs supply rebaseline
+            ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+                    Status:      Failed❌
+        "
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baseline() -> SupplyBaseline {
+        SupplyBaseline {
+            bridge_locked: U256::from(1_000),
+            ledger_outstanding: U256::from(400),
+            ledger_migrated: 50,
+            admin_ata_balance: 10_000,
+            admin_outflow: 0,
+            last_signature: None,
+        }
+    }
+
+    fn totals(lock_drift: u64, migration_drift: u64) -> SupplyTotals {
+        SupplyTotals {
+            bridge_locked: U256::zero(),
+            ledger_outstanding: U256::zero(),
+            admin_ata_balance: 0,
+            admin_outflow: 0,
+            ledger_migrated: 0,
+            lock_drift: U256::from(lock_drift),
+            migration_drift,
+        }
+    }
+
+    #[test]
+    fn recorded_changes_leave_no_drift() {
+        // 300 locked and recorded, 200 migrated and sent out of the admin ATA
+        let baseline = SupplyBaseline {
+            admin_outflow: 200,
+            ..baseline()
+        };
+        let totals = SupplyTotals::since(&baseline, U256::from(1_300), U256::from(700), 9_800, 250);
+
+        assert_eq!(totals.ledger_migrated, 200);
+        assert_eq!(totals.admin_outflow, 200);
+        assert_eq!(totals.lock_drift, U256::zero());
+        assert_eq!(totals.migration_drift, 0);
+    }
+
+    #[test]
+    fn unrecorded_changes_drift_either_way() {
+        let sent = SupplyBaseline {
+            admin_outflow: 30,
+            ..baseline()
+        };
+
+        // the bridge lost 100 the ledger does not know about, and 30 left the admin ATA unrecorded
+        let totals = SupplyTotals::since(&sent, U256::from(900), U256::from(400), 9_970, 50);
+        assert_eq!(totals.lock_drift, U256::from(100));
+        assert_eq!(totals.migration_drift, 30);
+
+        // the ledger recorded 100 of locks and 30 of migrations neither chain shows
+        let totals =
+            SupplyTotals::since(&baseline(), U256::from(1_000), U256::from(500), 10_000, 80);
+        assert_eq!(totals.lock_drift, U256::from(100));
+        assert_eq!(totals.migration_drift, 30);
+    }
+
+    #[test]
+    fn drift_at_the_tolerance_is_within_it() {
+        let tolerance = U256::from(10);
+        assert_eq!(assess(&totals(10, 5), tolerance, 5, true), Drift::Within);
+        assert_eq!(assess(&totals(11, 0), tolerance, 5, false), Drift::Breached);
+        assert_eq!(assess(&totals(0, 6), tolerance, 5, false), Drift::Breached);
+    }
+
+    #[test]
+    fn only_a_second_consecutive_breach_is_sustained() {
+        let tolerance = U256::from(10);
+        let mut drifted = false;
+        let mut outcomes = Vec::new();
+        for lock_drift in [11, 0, 11, 11, 11, 0] {
+            let drift = assess(&totals(lock_drift, 0), tolerance, 0, drifted);
+            drifted = drift != Drift::Within;
+            outcomes.push(drift);
+        }
+
+        assert_eq!(
+            outcomes,
+            [
+                Drift::Breached,
+                Drift::Within,
+                Drift::Breached,
+                Drift::Sustained,
+                Drift::Sustained,
+                Drift::Within,
+            ]
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_sdk::{account::Account, pubkey::Pubkey, sdk_ids::SDK_IDS};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Reasons a Solana destination cannot be migrated to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DestinationError {
+    /// Address is not a base58 public key.
+    Unparseable(String),
+    /// Address is off the ed25519 curve and not allow-listed, so no wallet can sign for it.
+    OffCurve,
+    /// Address is a known program, sysvar or relayer-owned account.
+    Reserved,
+    /// Address holds an executable account.
+    Executable,
+    /// Destination account could not be fetched.
+    Lookup(String),
+}
+
+impl DestinationError {
+    /// Whether the lock should be refunded rather than retried.
+    pub fn is_refundable(&self) -> bool {
+        !matches!(self, DestinationError::Lookup(_))
+    }
+}
+
+impl fmt::Display for DestinationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DestinationError::Unparseable(err) => write!(f, "address is not a public key: {err}"),
+            DestinationError::OffCurve => write!(f, "address is off curve and not allow-listed"),
+            DestinationError::Reserved => {
+                write!(f, "address is a program, sysvar or relayer account")
+            }
+            DestinationError::Executable => write!(f, "address is an executable account"),
+            DestinationError::Lookup(err) => {
+                write!(f, "failed to fetch destination account: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DestinationError {}
+
+/// Pre-flight checks on Solana destinations decoded from `TokensLocked` events.
+pub struct DestinationValidator {
+    reserved: HashSet<Pubkey>,
+    off_curve_allowlist: HashSet<Pubkey>,
+}
+
+impl DestinationValidator {
+    /// Builds a validator rejecting `reserved` accounts in addition to SDK programs and sysvars.
+    pub fn new(
+        reserved: impl IntoIterator<Item = Pubkey>,
+        off_curve_allowlist: impl IntoIterator<Item = Pubkey>,
+    ) -> Self {
+        let mut reserved: HashSet<Pubkey> = reserved.into_iter().collect();
+        reserved.extend(SDK_IDS.iter());
+
+        Self {
+            reserved,
+            off_curve_allowlist: off_curve_allowlist.into_iter().collect(),
+        }
+    }
+
+    /// Parses and validates `address`, returning the destination public key.
+    pub async fn validate(
+        &self,
+        connection: &RpcClient,
+        address: &str,
+    ) -> Result<Pubkey, DestinationError> {
+        let destination = self.check_address(address)?;
+
+        let account = connection
+            .get_account_with_commitment(&destination, connection.commitment())
+            .await
+            .map_err(|err| DestinationError::Lookup(err.to_string()))?;
+
+        check_account(destination, account.value.as_ref())
+    }
+
+    /// The checks on `address` that need no RPC.
+    fn check_address(&self, address: &str) -> Result<Pubkey, DestinationError> {
+        let destination = Pubkey::from_str(address)
+            .map_err(|err| DestinationError::Unparseable(err.to_string()))?;
+
+        if self.reserved.contains(&destination) {
+            return Err(DestinationError::Reserved);
+        }
+
+        if !destination.is_on_curve() && !self.off_curve_allowlist.contains(&destination) {
+            return Err(DestinationError::OffCurve);
+        }
+
+        Ok(destination)
+    }
+}
+
+/// Rejects `destination` when its account, if it exists, is executable.
+fn check_account(
+    destination: Pubkey,
+    account: Option<&Account>,
+) -> Result<Pubkey, DestinationError> {
+    match account {
+        Some(account) if account.executable => Err(DestinationError::Executable),
+        _ => Ok(destination),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::solana_sdk::{signature::Keypair, signer::Signer, system_program, sysvar};
+
+    fn off_curve() -> Pubkey {
+        Pubkey::find_program_address(&[b"migration"], &Pubkey::new_unique()).0
+    }
+
+    #[test]
+    fn rejects_unparseable_addresses() {
+        let validator = DestinationValidator::new([], []);
+        assert!(matches!(
+            validator.check_address("not-a-key"),
+            Err(DestinationError::Unparseable(_))
+        ));
+        assert!(matches!(
+            validator.check_address(""),
+            Err(DestinationError::Unparseable(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_sdk_ids_and_reserved_accounts() {
+        let relayer = Keypair::new().pubkey();
+        let validator = DestinationValidator::new([relayer], []);
+        for reserved in [system_program::ID, sysvar::clock::ID, relayer] {
+            assert_eq!(
+                validator.check_address(&reserved.to_string()),
+                Err(DestinationError::Reserved)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_off_curve_addresses_unless_allow_listed() {
+        let listed = off_curve();
+        let unlisted = off_curve();
+        let validator = DestinationValidator::new([], [listed]);
+
+        assert_eq!(
+            validator.check_address(&unlisted.to_string()),
+            Err(DestinationError::OffCurve)
+        );
+        assert_eq!(validator.check_address(&listed.to_string()), Ok(listed));
+    }
+
+    #[test]
+    fn accepts_wallet_addresses() {
+        let wallet = Keypair::new().pubkey();
+        let validator = DestinationValidator::new([], []);
+        assert_eq!(validator.check_address(&wallet.to_string()), Ok(wallet));
+    }
+
+    #[test]
+    fn rejects_executable_accounts() {
+        let destination = Keypair::new().pubkey();
+        let executable = Account {
+            executable: true,
+            ..Account::default()
+        };
+        assert_eq!(
+            check_account(destination, Some(&executable)),
+            Err(DestinationError::Executable)
+        );
+        assert_eq!(
+            check_account(destination, Some(&Account::default())),
+            Ok(destination)
+        );
+        assert_eq!(check_account(destination, None), Ok(destination));
+    }
+
+    #[test]
+    fn only_lookup_failures_are_retried() {
+        assert!(DestinationError::OffCurve.is_refundable());
+        assert!(DestinationError::Reserved.is_refundable());
+        assert!(!DestinationError::Lookup("timeout".to_string()).is_refundable());
+    }
+}